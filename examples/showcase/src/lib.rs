@@ -54,7 +54,7 @@ struct TestDialog {
 }
 
 impl dialog::FarDialog for TestDialog {
-    fn dlg_proc(&mut self, h_dlg: farmanager::HANDLE, msg: dialog::FarMessage) -> isize {
+    fn dlg_proc(&mut self, h_dlg: dialog::DialogHandle, msg: dialog::FarMessage) -> isize {
         trace!(">dlg_proc()");
         let result: isize = match msg {
             dialog::FarMessage::DnBtnClick { id, state } => {
@@ -1273,7 +1273,7 @@ impl Plugin {
                                      basic::INPUTBOXFLAGS::FIB_NONE);
 
         match input {
-            Some(path) => editor::open_editor(path),
+            Some(path) => { let _ = editor::EditorOpenOptions::new(path).open(); },
             None => {},
         }
     }
@@ -1320,7 +1320,7 @@ impl Plugin {
                                      basic::INPUTBOXFLAGS::FIB_NONE);
 
         match input {
-            Some(path) => viewer::open_viewer(path),
+            Some(path) => { let _ = viewer::ViewerOpenOptions::new(path).open(); },
             None => {},
         }
     }
@@ -1497,7 +1497,19 @@ impl Plugin {
         match dialog::Dialog::init(self.guid, dialog_guid, -1, -1, 40, 20,
                                      None, dialog_items,
                                      dialog::FARDIALOGFLAGS::FDLG_NONE, test_dialog) {
-            Ok(dialog) => dialog.run(),
+            Ok(dialog) => {
+                let exit_item = dialog.run();
+                let text = WideString::from(format!("{}\nExit item: {}\ncheckbox1: {}\nRadio: {}\nEdit: {}\n{}\n{}",
+                                                  "Dialog::run".to_string(),
+                                                  exit_item.map_or("Esc".to_string(), |id| id.to_string()),
+                                                  dialog.get_check(1).map_or("unknown".to_string(), |state| (state as u8).to_string()),
+                                                  dialog.get_radio_selection(4..6).map_or("none".to_string(), |id| id.to_string()),
+                                                  dialog.get_text(11).map(|text| text.to_string_lossy()).unwrap_or_default(),
+                                                  basic::DIALOG_SEPARATOR.to_string(),
+                                                  basic::get_msg(&Lng::MessageButton)));
+                basic::message(basic::FARMESSAGEFLAGS::FMSG_LEFTALIGN | basic::FARMESSAGEFLAGS::FMSG_ALLINONE,
+                               None, basic::MessageItems::AllInOne(text), 1);
+            },
             Err(_) => {},
         }
     }
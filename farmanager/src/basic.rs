@@ -6,7 +6,9 @@ use std::panic;
 use std::ptr;
 use std::slice;
 
+use bitflags::bitflags;
 use log::*;
+use winapi::um::wincon;
 
 use crate::common;
 use crate::common::Enrichable;
@@ -146,6 +148,179 @@ pub struct ExitInfo;
 
 pub const DIALOG_SEPARATOR: &'static str = "\x01";
 
+bitflags! {
+    pub struct CONTROL_KEY_STATE: ffi::DWORD {
+        const RIGHT_ALT_PRESSED  = wincon::RIGHT_ALT_PRESSED;
+        const LEFT_ALT_PRESSED   = wincon::LEFT_ALT_PRESSED;
+        const RIGHT_CTRL_PRESSED = wincon::RIGHT_CTRL_PRESSED;
+        const LEFT_CTRL_PRESSED  = wincon::LEFT_CTRL_PRESSED;
+        const SHIFT_PRESSED      = wincon::SHIFT_PRESSED;
+        const NUMLOCK_ON         = wincon::NUMLOCK_ON;
+        const SCROLLLOCK_ON      = wincon::SCROLLLOCK_ON;
+        const CAPSLOCK_ON        = wincon::CAPSLOCK_ON;
+        const ENHANCED_KEY       = wincon::ENHANCED_KEY;
+    }
+}
+
+impl CONTROL_KEY_STATE {
+    pub fn alt(&self) -> bool {
+        self.intersects(CONTROL_KEY_STATE::LEFT_ALT_PRESSED | CONTROL_KEY_STATE::RIGHT_ALT_PRESSED)
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.intersects(CONTROL_KEY_STATE::LEFT_CTRL_PRESSED | CONTROL_KEY_STATE::RIGHT_CTRL_PRESSED)
+    }
+
+    pub fn shift(&self) -> bool {
+        self.contains(CONTROL_KEY_STATE::SHIFT_PRESSED)
+    }
+}
+
+bitflags! {
+    pub struct MOUSE_BUTTON_STATE: ffi::DWORD {
+        const FROM_LEFT_1ST_BUTTON_PRESSED = wincon::FROM_LEFT_1ST_BUTTON_PRESSED;
+        const RIGHTMOST_BUTTON_PRESSED     = wincon::RIGHTMOST_BUTTON_PRESSED;
+        const FROM_LEFT_2ND_BUTTON_PRESSED = wincon::FROM_LEFT_2ND_BUTTON_PRESSED;
+        const FROM_LEFT_3RD_BUTTON_PRESSED = wincon::FROM_LEFT_3RD_BUTTON_PRESSED;
+        const FROM_LEFT_4TH_BUTTON_PRESSED = wincon::FROM_LEFT_4TH_BUTTON_PRESSED;
+    }
+}
+
+bitflags! {
+    pub struct MOUSE_EVENT_FLAGS: ffi::DWORD {
+        const MOUSE_MOVED    = wincon::MOUSE_MOVED;
+        const DOUBLE_CLICK   = wincon::DOUBLE_CLICK;
+        const MOUSE_WHEELED  = wincon::MOUSE_WHEELED;
+        const MOUSE_HWHEELED = wincon::MOUSE_HWHEELED;
+    }
+}
+
+pub struct KeyEvent {
+    pub key_down: bool,
+    pub repeat_count: u16,
+    pub virtual_key_code: u16,
+    pub virtual_scan_code: u16,
+    pub unicode_char: Option<char>,
+    pub control_key_state: CONTROL_KEY_STATE,
+    // the event is sent by a macro (FARMACRO_KEY_EVENT)
+    pub from_macro: bool,
+}
+
+pub struct MouseEvent {
+    pub x: i16,
+    pub y: i16,
+    pub button_state: MOUSE_BUTTON_STATE,
+    pub control_key_state: CONTROL_KEY_STATE,
+    pub event_flags: MOUSE_EVENT_FLAGS,
+}
+
+pub enum InputEvent {
+    Key(KeyEvent),
+    Mouse(MouseEvent),
+    WindowBufferSize {
+        x: i16,
+        y: i16,
+    },
+    Menu(u32),
+    Focus(bool),
+}
+
+impl InputEvent {
+    #[allow(dead_code)]
+    pub(crate) fn from(record: &ffi::INPUT_RECORD) -> Option<Self> {
+        let event = match record.EventType {
+            wincon::KEY_EVENT => InputEvent::Key(KeyEvent::from(unsafe { record.Event.KeyEvent() })),
+            t if t as ffi::DWORD == ffi::FARMACRO_KEY_EVENT => InputEvent::Key(KeyEvent {
+                from_macro: true,
+                ..KeyEvent::from(unsafe { record.Event.KeyEvent() })
+            }),
+            wincon::MOUSE_EVENT => InputEvent::Mouse(MouseEvent::from(unsafe { record.Event.MouseEvent() })),
+            wincon::WINDOW_BUFFER_SIZE_EVENT => {
+                let size = unsafe { record.Event.WindowBufferSizeEvent() }.dwSize;
+                InputEvent::WindowBufferSize {
+                    x: size.X,
+                    y: size.Y,
+                }
+            },
+            wincon::MENU_EVENT => InputEvent::Menu(unsafe { record.Event.MenuEvent() }.dwCommandId),
+            wincon::FOCUS_EVENT => InputEvent::Focus(unsafe { record.Event.FocusEvent() }.bSetFocus != ffi::FALSE),
+            _ => return None
+        };
+        Some(event)
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn to_record(&self) -> ffi::INPUT_RECORD {
+        let mut record: ffi::INPUT_RECORD = unsafe { mem::zeroed() };
+        match self {
+            InputEvent::Key(event) => {
+                record.EventType = match event.from_macro {
+                    true => ffi::FARMACRO_KEY_EVENT as ffi::WORD,
+                    false => wincon::KEY_EVENT
+                };
+                let key = unsafe { record.Event.KeyEvent_mut() };
+                key.bKeyDown = event.key_down as ffi::BOOL;
+                key.wRepeatCount = event.repeat_count;
+                key.wVirtualKeyCode = event.virtual_key_code;
+                key.wVirtualScanCode = event.virtual_scan_code;
+                unsafe { *key.uChar.UnicodeChar_mut() = event.unicode_char.map_or(0, |c| c as u32 as u16) };
+                key.dwControlKeyState = event.control_key_state.bits();
+            },
+            InputEvent::Mouse(event) => {
+                record.EventType = wincon::MOUSE_EVENT;
+                let mouse = unsafe { record.Event.MouseEvent_mut() };
+                mouse.dwMousePosition = wincon::COORD { X: event.x, Y: event.y };
+                mouse.dwButtonState = event.button_state.bits();
+                mouse.dwControlKeyState = event.control_key_state.bits();
+                mouse.dwEventFlags = event.event_flags.bits();
+            },
+            InputEvent::WindowBufferSize { x, y } => {
+                record.EventType = wincon::WINDOW_BUFFER_SIZE_EVENT;
+                unsafe { record.Event.WindowBufferSizeEvent_mut() }.dwSize = wincon::COORD { X: *x, Y: *y };
+            },
+            InputEvent::Menu(command_id) => {
+                record.EventType = wincon::MENU_EVENT;
+                unsafe { record.Event.MenuEvent_mut() }.dwCommandId = *command_id;
+            },
+            InputEvent::Focus(set_focus) => {
+                record.EventType = wincon::FOCUS_EVENT;
+                unsafe { record.Event.FocusEvent_mut() }.bSetFocus = *set_focus as ffi::BOOL;
+            },
+        }
+        record
+    }
+}
+
+impl From<&wincon::KEY_EVENT_RECORD> for KeyEvent {
+    fn from(record: &wincon::KEY_EVENT_RECORD) -> Self {
+        let unicode_char = unsafe { *record.uChar.UnicodeChar() };
+        KeyEvent {
+            key_down: record.bKeyDown != ffi::FALSE,
+            repeat_count: record.wRepeatCount,
+            virtual_key_code: record.wVirtualKeyCode,
+            virtual_scan_code: record.wVirtualScanCode,
+            unicode_char: match unicode_char {
+                0 => None,
+                c => std::char::from_u32(c as u32)
+            },
+            control_key_state: CONTROL_KEY_STATE::from_bits_truncate(record.dwControlKeyState),
+            from_macro: false,
+        }
+    }
+}
+
+impl From<&wincon::MOUSE_EVENT_RECORD> for MouseEvent {
+    fn from(record: &wincon::MOUSE_EVENT_RECORD) -> Self {
+        MouseEvent {
+            x: record.dwMousePosition.X,
+            y: record.dwMousePosition.Y,
+            button_state: MOUSE_BUTTON_STATE::from_bits_truncate(record.dwButtonState),
+            control_key_state: CONTROL_KEY_STATE::from_bits_truncate(record.dwControlKeyState),
+            event_flags: MOUSE_EVENT_FLAGS::from_bits_truncate(record.dwEventFlags),
+        }
+    }
+}
+
 pub(crate) fn init_context() {
     CONTEXT.with(|ref_cell: &RefCell<Option<ctx::Context>>| {
         ref_cell.replace(Some(ctx::Context::default()));
@@ -260,7 +435,7 @@ pub fn menu(x: Option<isize>, y: Option<isize>, max_height: Option<isize>, flags
                 reserved: [0; 2],
             });
         }
-        let break_code: libc::intptr_t = -1;
+        let mut break_code: libc::intptr_t = -1;
         let result;
         result = far_api.menu(guid, &event_guid, x.unwrap_or(-1), y.unwrap_or(-1),
                                 max_height.unwrap_or(0),
@@ -269,7 +444,7 @@ pub fn menu(x: Option<isize>, y: Option<isize>, max_height: Option<isize>, flags
                                 bottom_ws.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
                                 help_topic_ws.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
                                 break_keys_raw.as_ptr(),
-                                &break_code,
+                                &mut break_code,
                                 items_raw.as_ptr(),
                                 items_raw.len());
         let result = (if result == -1 { None } else { Some(result as usize) },
@@ -280,6 +455,20 @@ pub fn menu(x: Option<isize>, y: Option<isize>, max_height: Option<isize>, flags
     return result;
 }
 
+// Position of the console cursor in the Far window, the editor and the viewer keep it at the caret.
+pub fn get_cursor_pos() -> Option<(isize, isize)> {
+    let plugin_id = plugin_guid();
+    let mut coord = wincon::COORD { X: 0, Y: 0 };
+    let result = far_api(|far_api: &mut ffi::PluginStartupInfo| {
+        (far_api.adv_control)(&plugin_id, ffi::ADVANCED_CONTROL_COMMANDS::ACTL_GETCURSORPOS, 0,
+                              &mut coord as *mut _ as *mut libc::c_void)
+    });
+    match result {
+        0 => None,
+        _ => Some((coord.X as isize, coord.Y as isize))
+    }
+}
+
 pub fn show_help(module_name: &str, topic: Option<&str>, flags: ffi::FARHELPFLAGS) -> bool {
     trace!(">show_help()");
     let module_name_ws = WideString::from(module_name);
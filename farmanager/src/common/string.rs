@@ -27,6 +27,18 @@ impl WideString {
         self.inner.as_ptr()
     }
 
+    pub fn as_slice(&self) -> &[ffi::wchar_t] {
+        self.inner.as_slice()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
     #[allow(dead_code)]
     pub fn into_raw(self) -> *mut ffi::wchar_t {
         self.inner.into_raw()
@@ -1,18 +1,30 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::marker::PhantomData;
 use std::mem;
+use std::ops::Range;
 use std::ptr;
+use std::slice;
 
+use bitflags::bitflags;
 use failure::*;
 use libc::*;
 use log::*;
+use winapi::um::wincon::{COORD, SMALL_RECT};
 
+use crate::basic::InputEvent;
 use crate::common::string::WideString;
 use crate::far_api;
 use crate::ffi;
+pub use crate::ffi::FAR_CHAR_INFO as FAR_CHAR_INFO;
+pub use crate::ffi::FarColor as FarColor;
 pub use crate::ffi::FARDIALOGFLAGS as FARDIALOGFLAGS;
 pub use crate::ffi::FARDIALOGITEMFLAGS as FARDIALOGITEMFLAGS;
 pub use crate::ffi::FARMESSAGE as FARMESSAGE;
+pub use crate::ffi::LISTITEMFLAGS as LISTITEMFLAGS;
+
+pub mod builder;
+pub mod list;
 
 pub enum ButtonSelection {
     Deselected = 0,
@@ -52,6 +64,15 @@ impl From<u8> for CheckBoxSelection {
     }
 }
 
+fn decode_check(value: libc::intptr_t) -> Option<CheckBoxSelection> {
+    match value {
+        0 => Some(CheckBoxSelection::Deselected),
+        1 => Some(CheckBoxSelection::Selected),
+        2 => Some(CheckBoxSelection::Undefined),
+        _ => None
+    }
+}
+
 impl Into<DialogItemSelection> for CheckBoxSelection {
     fn into(self) -> DialogItemSelection {
         DialogItemSelection { value: self as u8 }
@@ -102,6 +123,61 @@ impl DialogItemSelection {
     }
 }
 
+#[derive(Clone)]
+pub struct ListItem {
+    pub flags: LISTITEMFLAGS,
+    pub text: WideString,
+}
+
+impl ListItem {
+    fn as_raw(&self) -> ffi::FarListItem {
+        ffi::FarListItem {
+            flags: self.flags,
+            text: self.text.as_ptr(),
+            user_dara: 0,
+            reserved: 0,
+        }
+    }
+
+    unsafe fn from_raw(item: &ffi::FarListItem) -> Self {
+        ListItem {
+            flags: item.flags,
+            text: decode_text(item.text).unwrap_or_else(WideString::new),
+        }
+    }
+}
+
+// FarList passed to Far for a list item, the text pointers refer to the dialog items which are kept
+// together with it till the dialog is freed.
+struct RawList {
+    #[allow(dead_code)]
+    items: Vec<ffi::FarListItem>,
+    list: Box<ffi::FarList>,
+}
+
+impl RawList {
+    fn new(items: &[ListItem]) -> Self {
+        let mut items: Vec<ffi::FarListItem> = items.iter().map(ListItem::as_raw).collect();
+        let list = Box::new(ffi::FarList {
+            struct_size: mem::size_of::<ffi::FarList>(),
+            items_number: items.len(),
+            items: items.as_mut_ptr(),
+        });
+        RawList {
+            items,
+            list,
+        }
+    }
+
+    fn as_ptr(&mut self) -> *mut ffi::FarList {
+        &mut *self.list as *mut ffi::FarList
+    }
+}
+
+fn user_control_cells(x1: isize, y1: isize, x2: isize, y2: isize) -> usize {
+    ((x2 - x1 + 1).max(0) * (y2 - y1 + 1).max(0)) as usize
+}
+
 pub enum FarDialogItem {
     CheckBox { x: isize, y: isize, selected: DialogItemSelection, flags: FARDIALOGITEMFLAGS, text: WideString },
     Text { x1: isize, y: isize, x2: isize, mask: Option<WideString>, flags: FARDIALOGITEMFLAGS, text: Option<WideString> },
@@ -113,10 +189,14 @@ pub enum FarDialogItem {
     PswEdit { x1: isize, y: isize, x2: isize, flags: FARDIALOGITEMFLAGS, text: Option<WideString> },
     RadioButton { x: isize, y: isize, selected: DialogItemSelection, flags: FARDIALOGITEMFLAGS, text: WideString },
     Button { x: isize, y: isize, selected: DialogItemSelection, flags: FARDIALOGITEMFLAGS, text: WideString },
+    ComboBox { x1: isize, y: isize, x2: isize, items: Vec<ListItem>, flags: FARDIALOGITEMFLAGS, text: Option<WideString> },
+    ListBox { x1: isize, y1: isize, x2: isize, y2: isize, items: Vec<ListItem>, flags: FARDIALOGITEMFLAGS, title: Option<WideString> },
+    // buffer holds (x2 - x1 + 1) * (y2 - y1 + 1) cells or is empty
+    UserControl { x1: isize, y1: isize, x2: isize, y2: isize, buffer: Vec<FAR_CHAR_INFO>, flags: FARDIALOGITEMFLAGS },
 }
 
 impl FarDialogItem {
-    fn as_raw(&self) -> ffi::FarDialogItem {
+    fn as_raw(&self, lists: &mut Vec<RawList>) -> ffi::FarDialogItem {
         match self {
             FarDialogItem::CheckBox { x, y, selected, flags, text } => ffi::FarDialogItem {
                 item_type: ffi::FARDIALOGITEMTYPES::DI_CHECKBOX,
@@ -304,310 +384,1049 @@ impl FarDialogItem {
                 user_data: 0,
                 reserved: [0; 2]
             },
+            FarDialogItem::ComboBox { x1, y, x2, items, flags, text } => ffi::FarDialogItem {
+                item_type: ffi::FARDIALOGITEMTYPES::DI_COMBOBOX,
+                x1: *x1,
+                y1: *y,
+                x2: *x2,
+                y2: *y,
+                param: ffi::FarDialogItemParam { list_items: {
+                    let mut list = RawList::new(items);
+                    let ptr = list.as_ptr();
+                    lists.push(list);
+                    ptr
+                } },
+                history: ptr::null(),
+                mask: ptr::null(),
+                flags: *flags,
+                data: match text {
+                    Some(v) => v.as_ptr(),
+                    None => ptr::null(),
+                },
+                max_length: 0,
+                user_data: 0,
+                reserved: [0; 2]
+            },
+            FarDialogItem::ListBox { x1, y1, x2, y2, items, flags, title } => ffi::FarDialogItem {
+                item_type: ffi::FARDIALOGITEMTYPES::DI_LISTBOX,
+                x1: *x1,
+                y1: *y1,
+                x2: *x2,
+                y2: *y2,
+                param: ffi::FarDialogItemParam { list_items: {
+                    let mut list = RawList::new(items);
+                    let ptr = list.as_ptr();
+                    lists.push(list);
+                    ptr
+                } },
+                history: ptr::null(),
+                mask: ptr::null(),
+                flags: *flags,
+                data: match title {
+                    Some(v) => v.as_ptr(),
+                    None => ptr::null(),
+                },
+                max_length: 0,
+                user_data: 0,
+                reserved: [0; 2]
+            },
+            FarDialogItem::UserControl { x1, y1, x2, y2, buffer, flags } => ffi::FarDialogItem {
+                item_type: ffi::FARDIALOGITEMTYPES::DI_USERCONTROL,
+                x1: *x1,
+                y1: *y1,
+                x2: *x2,
+                y2: *y2,
+                // Far reads the whole area from the buffer, a buffer of a different size isn't passed
+                param: ffi::FarDialogItemParam { v_buf: match buffer.len() == user_control_cells(*x1, *y1, *x2, *y2) {
+                    true => buffer.as_ptr() as *mut FAR_CHAR_INFO,
+                    false => ptr::null_mut(),
+                } },
+                history: ptr::null(),
+                mask: ptr::null(),
+                flags: *flags,
+                data: ptr::null(),
+                max_length: 0,
+                user_data: 0,
+                reserved: [0; 2]
+            },
         }
     }
+
+    fn is_valid(&self) -> bool {
+        match self {
+            FarDialogItem::UserControl { x1, y1, x2, y2, buffer, .. } => {
+                buffer.is_empty() || buffer.len() == user_control_cells(*x1, *y1, *x2, *y2)
+            },
+            _ => true
+        }
+    }
+
+    // Copies the item passed by Far, the list items and the user control buffer are copied as well.
+    unsafe fn from_raw(item: &ffi::FarDialogItem) -> Self {
+        let flags = item.flags;
+        let text = decode_text(item.data);
+        let history = decode_text(item.history);
+        let mask = decode_text(item.mask);
+        let selected = DialogItemSelection { value: item.param.selected as u8 };
+        match item.item_type {
+            ffi::FARDIALOGITEMTYPES::DI_TEXT => FarDialogItem::Text { x1: item.x1, y: item.y1, x2: item.x2, mask, flags, text },
+            ffi::FARDIALOGITEMTYPES::DI_VTEXT => FarDialogItem::VText { x: item.x1, y1: item.y1, y2: item.y2, mask, flags, text },
+            ffi::FARDIALOGITEMTYPES::DI_SINGLEBOX => {
+                FarDialogItem::SingleBox { x1: item.x1, y1: item.y1, x2: item.x2, y2: item.y2, flags, title: text }
+            },
+            ffi::FARDIALOGITEMTYPES::DI_DOUBLEBOX => {
+                FarDialogItem::DoubleBox { x1: item.x1, y1: item.y1, x2: item.x2, y2: item.y2, flags, title: text }
+            },
+            ffi::FARDIALOGITEMTYPES::DI_EDIT => FarDialogItem::Edit { x1: item.x1, y: item.y1, x2: item.x2, history, flags, text },
+            ffi::FARDIALOGITEMTYPES::DI_PSWEDIT => FarDialogItem::PswEdit { x1: item.x1, y: item.y1, x2: item.x2, flags, text },
+            ffi::FARDIALOGITEMTYPES::DI_FIXEDIT => {
+                FarDialogItem::FixEdit { x1: item.x1, y: item.y1, x2: item.x2, history, mask, flags, text }
+            },
+            ffi::FARDIALOGITEMTYPES::DI_BUTTON => {
+                FarDialogItem::Button { x: item.x1, y: item.y1, selected, flags, text: text.unwrap_or_else(WideString::new) }
+            },
+            ffi::FARDIALOGITEMTYPES::DI_CHECKBOX => {
+                FarDialogItem::CheckBox { x: item.x1, y: item.y1, selected, flags, text: text.unwrap_or_else(WideString::new) }
+            },
+            ffi::FARDIALOGITEMTYPES::DI_RADIOBUTTON => {
+                FarDialogItem::RadioButton { x: item.x1, y: item.y1, selected, flags, text: text.unwrap_or_else(WideString::new) }
+            },
+            ffi::FARDIALOGITEMTYPES::DI_COMBOBOX => FarDialogItem::ComboBox {
+                x1: item.x1,
+                y: item.y1,
+                x2: item.x2,
+                items: decode_list(item.param.list_items),
+                flags,
+                text
+            },
+            ffi::FARDIALOGITEMTYPES::DI_LISTBOX => FarDialogItem::ListBox {
+                x1: item.x1,
+                y1: item.y1,
+                x2: item.x2,
+                y2: item.y2,
+                items: decode_list(item.param.list_items),
+                flags,
+                title: text
+            },
+            ffi::FARDIALOGITEMTYPES::DI_USERCONTROL => {
+                let cells = user_control_cells(item.x1, item.y1, item.x2, item.y2);
+                FarDialogItem::UserControl {
+                    x1: item.x1,
+                    y1: item.y1,
+                    x2: item.x2,
+                    y2: item.y2,
+                    buffer: decode_slice(item.param.v_buf, cells).map_or(Vec::new(), |buffer| buffer.to_vec()),
+                    flags
+                }
+            },
+        }
+    }
+}
+
+bitflags! {
+    #[allow(non_camel_case_types)]
+    pub struct COMBOBOXEVENTTYPE: libc::intptr_t {
+        const CBET_KEY   = ffi::FARCOMBOBOXEVENTTYPE::CBET_KEY as libc::intptr_t;
+        const CBET_MOUSE = ffi::FARCOMBOBOXEVENTTYPE::CBET_MOUSE as libc::intptr_t;
+    }
 }
 
-// TODO replace generic variant definitions
-pub enum FarMessage {
-    DmFirst { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmClose { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmEnable { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmEnableRedraw { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetDlgItem { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetDlgRect { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetText { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmKey { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmMoveDialog { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetDlgItem { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetFocus { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmRedraw { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetText { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetMaxTextLength { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmShowDialog { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetFocus { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetCursorPos { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetCursorPos { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetTextPtr { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmShowItem { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmAddHistory { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetCheck { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetCheck { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSet3State { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListSort { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListGetItem { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListGetCurpos { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListSetCurPos { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListDelete { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListAdd { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListAddStr { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListUpdate { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListInsert { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListFindString { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListInfo { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListGetData { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListSetData { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListSetTitles { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListGetTitles { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmResizeDialog { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetItemPosition { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetDropDownOpened { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetDropdownOpened { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetHistory { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetItemPosition { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetInputNotify { param1: libc::intptr_t, param2: *mut libc::c_void },
+// Pointer parameter of a message, it borrows the value for the time the message is sent or processed.
+// Only the values filled in by the receiver are writable.
+pub struct MessageParam<'a, T> {
+    ptr: *mut T,
+    mutable: bool,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> MessageParam<'a, T> {
+
+    pub fn null() -> Self {
+        MessageParam {
+            ptr: ptr::null_mut(),
+            mutable: false,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn from_ref(value: &'a T) -> Self {
+        MessageParam {
+            ptr: value as *const T as *mut T,
+            mutable: false,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn from_mut(value: &'a mut T) -> Self {
+        MessageParam {
+            ptr: value as *mut T,
+            mutable: true,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.ptr.is_null()
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        unsafe { self.ptr.as_ref() }
+    }
+
+    // Returns None for the read-only values.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        match self.mutable {
+            true => unsafe { self.ptr.as_mut() },
+            false => None
+        }
+    }
+
+    // ptr must be null or valid for 'a, and writable if mutable is set.
+    unsafe fn from_raw(ptr: *mut libc::c_void, mutable: bool) -> Self {
+        MessageParam {
+            ptr: ptr as *mut T,
+            mutable,
+            _marker: PhantomData,
+        }
+    }
+
+    fn as_raw(&self) -> *mut libc::c_void {
+        self.ptr as *mut libc::c_void
+    }
+}
+
+pub enum FarMessage<'a> {
+    DmFirst,
+    DmClose { exit_code: Option<ItemId> },
+    // None queries the state
+    DmEnable { id: ItemId, enable: Option<bool> },
+    DmEnableRedraw { enable: Option<bool> },
+    DmGetDlgItem { id: ItemId, item: MessageParam<'a, ffi::FarGetDialogItem> },
+    DmGetDlgRect { rect: MessageParam<'a, SMALL_RECT> },
+    DmGetText { id: ItemId, data: MessageParam<'a, ffi::FarDialogItemData> },
+    DmKey { records: &'a [ffi::INPUT_RECORD] },
+    // coord receives the new position of the dialog
+    DmMoveDialog { absolute: bool, coord: MessageParam<'a, COORD> },
+    // param is the item passed by Far, it is sent back instead of item unless it is null
+    DmSetDlgItem { id: ItemId, item: FarDialogItem, param: MessageParam<'a, ffi::FarDialogItem> },
+    DmSetFocus { id: ItemId },
+    DmRedraw,
+    DmSetText { id: ItemId, text: WideString },
+    DmSetMaxTextLength { id: ItemId, max_length: usize },
+    DmShowDialog { show: bool },
+    DmGetFocus,
+    DmGetCursorPos { id: ItemId, coord: MessageParam<'a, COORD> },
+    DmSetCursorPos { id: ItemId, coord: COORD },
+    DmSetTextPtr { id: ItemId, text: WideString },
+    // None toggles the visibility
+    DmShowItem { id: ItemId, show: Option<bool> },
+    DmAddHistory { id: ItemId, text: WideString },
+    DmGetCheck { id: ItemId },
+    DmSetCheck { id: ItemId, state: ffi::FARCHECKEDSTATE },
+    DmSet3State { id: ItemId, enable: bool },
+    DmListSort { id: ItemId, descending: bool },
+    DmListGetItem { id: ItemId, item: MessageParam<'a, ffi::FarListGetItem> },
+    DmListGetCurpos { id: ItemId, pos: MessageParam<'a, ffi::FarListPos> },
+    // top is the first visible item, None keeps it chosen by Far
+    DmListSetCurPos { id: ItemId, pos: usize, top: Option<usize> },
+    // None deletes all items
+    DmListDelete { id: ItemId, items: Option<Range<usize>> },
+    DmListAdd { id: ItemId, items: Vec<ListItem> },
+    DmListAddStr { id: ItemId, text: WideString },
+    DmListUpdate { id: ItemId, index: usize, item: ListItem },
+    DmListInsert { id: ItemId, index: usize, item: ListItem },
+    DmListFindString { id: ItemId, start: usize, pattern: WideString, flags: ffi::FARLISTFINDFLAGS },
+    DmListInfo { id: ItemId, info: MessageParam<'a, ffi::FarListInfo> },
+    DmListGetData { id: ItemId, index: usize },
+    DmListSetData { id: ItemId, index: usize, data: &'a [u8] },
+    DmListSetTitles { id: ItemId, title: Option<WideString>, bottom: Option<WideString> },
+    DmListGetTitles { id: ItemId, titles: MessageParam<'a, ffi::FarListTitles> },
+    DmResizeDialog { size: COORD },
+    DmSetItemPosition { id: ItemId, rect: ItemRect },
+    DmGetDropDownOpened { id: ItemId },
+    DmSetDropdownOpened { id: ItemId, open: bool },
+    // None clears the history name
+    DmSetHistory { id: ItemId, history: Option<WideString> },
+    DmGetItemPosition { id: ItemId, rect: MessageParam<'a, SMALL_RECT> },
+    DmSetInputNotify { notify: Option<bool> },
     /* DmSetMouseEventNotify = DmSetInputNotify,*/
-    DmEditUnchangedFlag { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetItemData { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetItemData { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListSet { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetCursorSize { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetCursorSize { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmListGetDataSize { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetSelection { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetSelection { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetEditPosition { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetEditPosition { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetComboboxEvent { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetComboboxEvent { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetConstTextPtr { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetDlgItemShort { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmSetDlgItemShort { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetDialogInfo { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DmGetDialogTitle { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnFirst { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnBtnClick { id: isize, state: DialogItemSelection },
-    DnCtlColorDialog { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnCtlColorDlgItem { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnCtlColorDlgList { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnDrawDialog { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnDrawDlgItem { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnEditChange { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnEnterIdle { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnGotFocus { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnHelp { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnHotKey { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnInitDialog { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnKillFocus { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnListChange { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnDragged { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnResizeConsole { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnDrawDialogDone { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnListHotKey { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnInput { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnControlInput { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnClose { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnGetValue { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnDropdownOpened { param1: libc::intptr_t, param2: *mut libc::c_void },
-    DnDrawDlgItemDone { param1: libc::intptr_t, param2: *mut libc::c_void },
+    DmEditUnchangedFlag { id: ItemId, unchanged: Option<bool> },
+    DmGetItemData { id: ItemId },
+    DmSetItemData { id: ItemId, data: libc::intptr_t },
+    DmListSet { id: ItemId, items: Vec<ListItem> },
+    DmGetCursorSize { id: ItemId },
+    DmSetCursorSize { id: ItemId, visible: bool, size: usize },
+    DmListGetDataSize { id: ItemId, index: usize },
+    DmGetSelection { id: ItemId, selection: MessageParam<'a, ffi::EditorSelect> },
+    DmSetSelection { id: ItemId, selection: MessageParam<'a, ffi::EditorSelect> },
+    DmGetEditPosition { id: ItemId, position: MessageParam<'a, ffi::EditorSetPosition> },
+    DmSetEditPosition { id: ItemId, position: MessageParam<'a, ffi::EditorSetPosition> },
+    DmSetComboboxEvent { id: ItemId, events: COMBOBOXEVENTTYPE },
+    DmGetComboboxEvent { id: ItemId },
+    DmGetConstTextPtr { id: ItemId },
+    DmGetDlgItemShort { id: ItemId, item: MessageParam<'a, ffi::FarDialogItem> },
+    DmSetDlgItemShort { id: ItemId, item: FarDialogItem, param: MessageParam<'a, ffi::FarDialogItem> },
+    DmGetDialogInfo { info: MessageParam<'a, ffi::DialogInfo> },
+    DmGetDialogTitle { data: MessageParam<'a, ffi::FarDialogItemData> },
+    DnFirst,
+    DnBtnClick { id: ItemId, state: DialogItemSelection },
+    DnCtlColorDialog { color: &'a mut FarColor },
+    DnCtlColorDlgItem { id: ItemId, flags: libc::c_ulonglong, colors: &'a mut [FarColor] },
+    DnCtlColorDlgList { id: ItemId, flags: libc::c_ulonglong, colors: &'a mut [FarColor] },
+    DnDrawDialog,
+    // item is a copy, the changes made through param are seen by Far
+    DnDrawDlgItem { id: ItemId, item: FarDialogItem, param: MessageParam<'a, ffi::FarDialogItem> },
+    DnEditChange { id: ItemId, item: FarDialogItem, param: MessageParam<'a, ffi::FarDialogItem> },
+    DnEnterIdle,
+    DnGotFocus { id: ItemId },
+    // topic is a copy of param, param is sent back to Far as DefDlgProc returns it
+    DnHelp { id: ItemId, topic: Option<WideString>, param: MessageParam<'a, ffi::wchar_t> },
+    DnHotKey { id: ItemId, event: InputEvent },
+    DnInitDialog { focus: ItemId },
+    DnKillFocus { id: ItemId },
+    DnListChange { id: ItemId, pos: usize },
+    DnDragged { stage: libc::intptr_t, cancelled: bool },
+    DnResizeConsole { size: COORD },
+    DnDrawDialogDone,
+    DnListHotKey { id: ItemId, pos: usize },
+    DnInput { event: InputEvent },
+    DnControlInput { id: ItemId, event: InputEvent },
+    // dlg_proc returns 0 to prevent closing, None means Esc
+    DnClose { id: Option<ItemId> },
+    DnGetValue { id: ItemId, value: &'a mut ffi::FarGetValue },
+    DnDropdownOpened { id: ItemId, opened: bool },
+    DnDrawDlgItemDone { id: ItemId, item: FarDialogItem, param: MessageParam<'a, ffi::FarDialogItem> },
     DmUser { param1: libc::intptr_t, param2: *mut libc::c_void },
+    // message with the parameters which can't be decoded, it is passed as is
+    Raw { msg: libc::intptr_t, param1: libc::intptr_t, param2: *mut libc::c_void },
 }
 
-impl FarMessage {
-    fn from(msg: ffi::FARMESSAGE, param1: libc::intptr_t, param2: *mut libc::c_void) -> Option<Self> {
-        match msg {
-            FARMESSAGE::DM_FIRST => { Some(FarMessage::DmFirst { param1 , param2 }) },
-            FARMESSAGE::DM_CLOSE => { Some(FarMessage::DmClose { param1 , param2 }) },
-            FARMESSAGE::DM_ENABLE => { Some(FarMessage::DmEnable { param1 , param2 }) },
-            FARMESSAGE::DM_ENABLEREDRAW => { Some(FarMessage::DmEnableRedraw { param1 , param2 }) },
-            FARMESSAGE::DM_GETDLGDATA => { None },
-            FARMESSAGE::DM_GETDLGITEM => { Some(FarMessage::DmGetDlgItem { param1 , param2 }) },
-            FARMESSAGE::DM_GETDLGRECT => { Some(FarMessage::DmGetDlgRect { param1 , param2 }) },
-            FARMESSAGE::DM_GETTEXT => { Some(FarMessage::DmGetText { param1 , param2 }) },
-            FARMESSAGE::DM_KEY => { Some(FarMessage::DmKey { param1 , param2 }) },
-            FARMESSAGE::DM_MOVEDIALOG => { Some(FarMessage::DmMoveDialog { param1 , param2 }) },
-            FARMESSAGE::DM_SETDLGDATA => { None },
-            FARMESSAGE::DM_SETDLGITEM => { Some(FarMessage::DmSetDlgItem { param1 , param2 }) },
-            FARMESSAGE::DM_SETFOCUS => { Some(FarMessage::DmSetFocus { param1 , param2 }) },
-            FARMESSAGE::DM_REDRAW => { Some(FarMessage::DmRedraw { param1 , param2 }) },
-            FARMESSAGE::DM_SETTEXT => { Some(FarMessage::DmSetText { param1 , param2 }) },
-            FARMESSAGE::DM_SETMAXTEXTLENGTH => { Some(FarMessage::DmSetMaxTextLength { param1 , param2 }) },
-            FARMESSAGE::DM_SHOWDIALOG => { Some(FarMessage::DmShowDialog { param1 , param2 }) },
-            FARMESSAGE::DM_GETFOCUS => { Some(FarMessage::DmGetFocus { param1 , param2 }) },
-            FARMESSAGE::DM_GETCURSORPOS => { Some(FarMessage::DmGetCursorPos { param1 , param2 }) },
-            FARMESSAGE::DM_SETCURSORPOS => { Some(FarMessage::DmSetCursorPos { param1 , param2 }) },
-            FARMESSAGE::DM_SETTEXTPTR => { Some(FarMessage::DmSetTextPtr { param1 , param2 }) },
-            FARMESSAGE::DM_SHOWITEM => { Some(FarMessage::DmShowItem { param1 , param2 }) },
-            FARMESSAGE::DM_ADDHISTORY => { Some(FarMessage::DmAddHistory { param1 , param2 }) },
-            FARMESSAGE::DM_GETCHECK => { Some(FarMessage::DmGetCheck { param1 , param2 }) },
-            FARMESSAGE::DM_SETCHECK => { Some(FarMessage::DmSetCheck { param1 , param2 }) },
-            FARMESSAGE::DM_SET3STATE => { Some(FarMessage::DmSet3State { param1 , param2 }) },
-            FARMESSAGE::DM_LISTSORT => { Some(FarMessage::DmListSort { param1 , param2 }) },
-            FARMESSAGE::DM_LISTGETITEM => { Some(FarMessage::DmListGetItem { param1 , param2 }) },
-            FARMESSAGE::DM_LISTGETCURPOS => { Some(FarMessage::DmListGetCurpos { param1 , param2 }) },
-            FARMESSAGE::DM_LISTSETCURPOS => { Some(FarMessage::DmListSetCurPos { param1 , param2 }) },
-            FARMESSAGE::DM_LISTDELETE => { Some(FarMessage::DmListDelete { param1 , param2 }) },
-            FARMESSAGE::DM_LISTADD => { Some(FarMessage::DmListAdd { param1 , param2 }) },
-            FARMESSAGE::DM_LISTADDSTR => { Some(FarMessage::DmListAddStr { param1 , param2 }) },
-            FARMESSAGE::DM_LISTUPDATE => { Some(FarMessage::DmListUpdate { param1 , param2 }) },
-            FARMESSAGE::DM_LISTINSERT => { Some(FarMessage::DmListInsert { param1 , param2 }) },
-            FARMESSAGE::DM_LISTFINDSTRING => { Some(FarMessage::DmListFindString { param1 , param2 }) },
-            FARMESSAGE::DM_LISTINFO => { Some(FarMessage::DmListInfo { param1 , param2 }) },
-            FARMESSAGE::DM_LISTGETDATA => { Some(FarMessage::DmListGetData { param1 , param2 }) },
-            FARMESSAGE::DM_LISTSETDATA => { Some(FarMessage::DmListSetData { param1 , param2 }) },
-            FARMESSAGE::DM_LISTSETTITLES => { Some(FarMessage::DmListSetTitles { param1 , param2 }) },
-            FARMESSAGE::DM_LISTGETTITLES => { Some(FarMessage::DmListGetTitles { param1 , param2 }) },
-            FARMESSAGE::DM_RESIZEDIALOG => { Some(FarMessage::DmResizeDialog { param1 , param2 }) },
-            FARMESSAGE::DM_SETITEMPOSITION => { Some(FarMessage::DmSetItemPosition { param1 , param2 }) },
-            FARMESSAGE::DM_GETDROPDOWNOPENED => { Some(FarMessage::DmGetDropDownOpened { param1 , param2 }) },
-            FARMESSAGE::DM_SETDROPDOWNOPENED => { Some(FarMessage::DmSetDropdownOpened { param1 , param2 }) },
-            FARMESSAGE::DM_SETHISTORY => { Some(FarMessage::DmSetHistory { param1 , param2 }) },
-            FARMESSAGE::DM_GETITEMPOSITION => { Some(FarMessage::DmGetItemPosition { param1 , param2 }) },
-            FARMESSAGE::DM_SETINPUTNOTIFY => { Some(FarMessage::DmSetInputNotify { param1 , param2 }) },
-            /* FARMESSAGE::DM_SETMOUSEEVENTNOTIFY          = FARMESSAGE::DM_SETINPUTNOTIFY,*/
-            FARMESSAGE::DM_EDITUNCHANGEDFLAG => { Some(FarMessage::DmEditUnchangedFlag { param1 , param2 }) },
-            FARMESSAGE::DM_GETITEMDATA => { Some(FarMessage::DmGetItemData { param1 , param2 }) },
-            FARMESSAGE::DM_SETITEMDATA => { Some(FarMessage::DmSetItemData { param1 , param2 }) },
-            FARMESSAGE::DM_LISTSET => { Some(FarMessage::DmListSet { param1 , param2 }) },
-            FARMESSAGE::DM_GETCURSORSIZE => { Some(FarMessage::DmGetCursorSize { param1 , param2 }) },
-            FARMESSAGE::DM_SETCURSORSIZE => { Some(FarMessage::DmSetCursorSize { param1 , param2 }) },
-            FARMESSAGE::DM_LISTGETDATASIZE => { Some(FarMessage::DmListGetDataSize { param1 , param2 }) },
-            FARMESSAGE::DM_GETSELECTION => { Some(FarMessage::DmGetSelection { param1 , param2 }) },
-            FARMESSAGE::DM_SETSELECTION => { Some(FarMessage::DmSetSelection { param1 , param2 }) },
-            FARMESSAGE::DM_GETEDITPOSITION => { Some(FarMessage::DmGetEditPosition { param1 , param2 }) },
-            FARMESSAGE::DM_SETEDITPOSITION => { Some(FarMessage::DmSetEditPosition { param1 , param2 }) },
-            FARMESSAGE::DM_SETCOMBOBOXEVENT => { Some(FarMessage::DmSetComboboxEvent { param1 , param2 }) },
-            FARMESSAGE::DM_GETCOMBOBOXEVENT => { Some(FarMessage::DmGetComboboxEvent { param1 , param2 }) },
-            FARMESSAGE::DM_GETCONSTTEXTPTR => { Some(FarMessage::DmGetConstTextPtr { param1 , param2 }) },
-            FARMESSAGE::DM_GETDLGITEMSHORT => { Some(FarMessage::DmGetDlgItemShort { param1 , param2 }) },
-            FARMESSAGE::DM_SETDLGITEMSHORT => { Some(FarMessage::DmSetDlgItemShort { param1 , param2 }) },
-            FARMESSAGE::DM_GETDIALOGINFO => { Some(FarMessage::DmGetDialogInfo { param1 , param2 }) },
-            FARMESSAGE::DM_GETDIALOGTITLE => { Some(FarMessage::DmGetDialogTitle { param1 , param2 }) },
-            FARMESSAGE::DN_FIRST => { Some(FarMessage::DnFirst { param1 , param2 }) },
-            FARMESSAGE::DN_BTNCLICK => {
-                Some(FarMessage::DnBtnClick { id: param1 , state: DialogItemSelection { value: param2 as u8 } } )
-            },
-            FARMESSAGE::DN_CTLCOLORDIALOG => { Some(FarMessage::DnCtlColorDialog { param1 , param2 }) },
-            FARMESSAGE::DN_CTLCOLORDLGITEM => { Some(FarMessage::DnCtlColorDlgItem { param1 , param2 }) },
-            FARMESSAGE::DN_CTLCOLORDLGLIST => { Some(FarMessage::DnCtlColorDlgList { param1 , param2 }) },
-            FARMESSAGE::DN_DRAWDIALOG => { Some(FarMessage::DnDrawDialog { param1 , param2 }) },
-            FARMESSAGE::DN_DRAWDLGITEM => { Some(FarMessage::DnDrawDlgItem { param1 , param2 }) },
-            FARMESSAGE::DN_EDITCHANGE => { Some(FarMessage::DnEditChange { param1 , param2 }) },
-            FARMESSAGE::DN_ENTERIDLE => { Some(FarMessage::DnEnterIdle { param1 , param2 }) },
-            FARMESSAGE::DN_GOTFOCUS => { Some(FarMessage::DnGotFocus { param1 , param2 }) },
-            FARMESSAGE::DN_HELP => { Some(FarMessage::DnHelp { param1 , param2 }) },
-            FARMESSAGE::DN_HOTKEY => { Some(FarMessage::DnHotKey { param1 , param2 }) },
-            FARMESSAGE::DN_INITDIALOG => { Some(FarMessage::DnInitDialog { param1 , param2 }) },
-            FARMESSAGE::DN_KILLFOCUS => { Some(FarMessage::DnKillFocus { param1 , param2 }) },
-            FARMESSAGE::DN_LISTCHANGE => { Some(FarMessage::DnListChange { param1 , param2 }) },
-            FARMESSAGE::DN_DRAGGED => { Some(FarMessage::DnDragged { param1 , param2 }) },
-            FARMESSAGE::DN_RESIZECONSOLE => { Some(FarMessage::DnResizeConsole { param1 , param2 }) },
-            FARMESSAGE::DN_DRAWDIALOGDONE => { Some(FarMessage::DnDrawDialogDone { param1 , param2 }) },
-            FARMESSAGE::DN_LISTHOTKEY => { Some(FarMessage::DnListHotKey { param1 , param2 }) },
-            FARMESSAGE::DN_INPUT => { Some(FarMessage::DnInput { param1 , param2 }) },
-            FARMESSAGE::DN_CONTROLINPUT => { Some(FarMessage::DnControlInput { param1 , param2 }) },
-            FARMESSAGE::DN_CLOSE => { Some(FarMessage::DnClose { param1 , param2 }) },
-            FARMESSAGE::DN_GETVALUE => { Some(FarMessage::DnGetValue { param1 , param2 }) },
-            FARMESSAGE::DN_DROPDOWNOPENED => { Some(FarMessage::DnDropdownOpened { param1 , param2 }) },
-            FARMESSAGE::DN_DRAWDLGITEMDONE => { Some(FarMessage::DnDrawDlgItemDone { param1 , param2 }) },
-            FARMESSAGE::DM_USER => { Some(FarMessage::DmUser { param1 , param2 }) },
+fn decode_bool(value: libc::intptr_t) -> Option<bool> {
+    match value {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None
+    }
+}
+
+// -1 is used by Far to query or toggle the state
+fn decode_state(value: libc::intptr_t) -> Option<Option<bool>> {
+    match value {
+        -1 => Some(None),
+        value => decode_bool(value).map(Some)
+    }
+}
+
+fn encode_state(state: Option<bool>) -> libc::intptr_t {
+    state.map_or(-1, |state| state as libc::intptr_t)
+}
+
+fn decode_exit_code(value: libc::intptr_t) -> Option<Option<ItemId>> {
+    match value {
+        -1 => Some(None),
+        value if value >= 0 => Some(Some(value as ItemId)),
+        _ => None
+    }
+}
+
+fn encode_exit_code(exit_code: Option<ItemId>) -> libc::intptr_t {
+    exit_code.map_or(-1, |id| id as libc::intptr_t)
+}
+
+fn decode_check_state(value: libc::intptr_t) -> Option<ffi::FARCHECKEDSTATE> {
+    match value {
+        v if v == ffi::FARCHECKEDSTATE::BSTATE_UNCHECKED as libc::intptr_t => Some(ffi::FARCHECKEDSTATE::BSTATE_UNCHECKED),
+        v if v == ffi::FARCHECKEDSTATE::BSTATE_CHECKED as libc::intptr_t => Some(ffi::FARCHECKEDSTATE::BSTATE_CHECKED),
+        v if v == ffi::FARCHECKEDSTATE::BSTATE_3STATE as libc::intptr_t => Some(ffi::FARCHECKEDSTATE::BSTATE_3STATE),
+        v if v == ffi::FARCHECKEDSTATE::BSTATE_TOGGLE as libc::intptr_t => Some(ffi::FARCHECKEDSTATE::BSTATE_TOGGLE),
+        _ => None
+    }
+}
+
+fn decode_index(value: libc::intptr_t) -> Option<usize> {
+    match value {
+        value if value >= 0 => Some(value as usize),
+        _ => None
+    }
+}
+
+unsafe fn decode_ref<'a, T>(ptr: *mut libc::c_void) -> Option<&'a T> {
+    (ptr as *const T).as_ref()
+}
+
+unsafe fn decode_mut<'a, T>(ptr: *mut libc::c_void) -> Option<&'a mut T> {
+    (ptr as *mut T).as_mut()
+}
+
+unsafe fn decode_text(text: *const ffi::wchar_t) -> Option<WideString> {
+    match text.is_null() {
+        true => None,
+        false => Some(WideString::from_ptr_str(text))
+    }
+}
+
+unsafe fn decode_slice<'a, T>(ptr: *const T, len: usize) -> Option<&'a [T]> {
+    match ptr.is_null() {
+        true if len == 0 => Some(&[]),
+        true => None,
+        false => Some(slice::from_raw_parts(ptr, len))
+    }
+}
+
+unsafe fn decode_list(list: *const ffi::FarList) -> Vec<ListItem> {
+    match list.as_ref() {
+        Some(list) => decode_slice(list.items, list.items_number).unwrap_or(&[]).iter()
+            .map(|item| ListItem::from_raw(item))
+            .collect(),
+        None => Vec::new()
+    }
+}
+
+unsafe fn decode_colors<'a>(ptr: *mut libc::c_void) -> Option<(libc::c_ulonglong, &'a mut [FarColor])> {
+    let colors = decode_mut::<ffi::FarDialogItemColors>(ptr)?;
+    match colors.colors.is_null() {
+        true => Some((colors.flags, &mut [])),
+        false => Some((colors.flags, slice::from_raw_parts_mut(colors.colors, colors.colors_count)))
+    }
+}
+
+unsafe fn decode_item<'a>(ptr: *mut libc::c_void, mutable: bool) -> Option<(FarDialogItem, MessageParam<'a, ffi::FarDialogItem>)> {
+    Some((FarDialogItem::from_raw(decode_ref(ptr)?), MessageParam::from_raw(ptr, mutable)))
+}
+
+fn encode_ptr<T>(value: &mut T) -> *mut libc::c_void {
+    value as *mut T as *mut libc::c_void
+}
+
+fn encode_text(text: &WideString) -> *mut libc::c_void {
+    text.as_ptr() as *mut libc::c_void
+}
+
+// Passes the item received from Far as is, the item built by the plugin is encoded.
+fn encode_item<R, S>(item: FarDialogItem, param: MessageParam<ffi::FarDialogItem>, send: S) -> R where S: FnOnce(*mut libc::c_void) -> R {
+    match param.is_null() {
+        false => send(param.as_raw()),
+        true => {
+            let mut lists: Vec<RawList> = Vec::new();
+            let mut item = item.as_raw(&mut lists);
+            send(encode_ptr(&mut item))
         }
     }
+}
+
+// Messages known to the binding, other values are passed to dlg_proc as Raw.
+const MESSAGES: [FARMESSAGE; 92] = [
+    FARMESSAGE::DM_FIRST, FARMESSAGE::DM_CLOSE, FARMESSAGE::DM_ENABLE, FARMESSAGE::DM_ENABLEREDRAW,
+    FARMESSAGE::DM_GETDLGDATA, FARMESSAGE::DM_GETDLGITEM, FARMESSAGE::DM_GETDLGRECT, FARMESSAGE::DM_GETTEXT,
+    FARMESSAGE::DM_KEY, FARMESSAGE::DM_MOVEDIALOG, FARMESSAGE::DM_SETDLGDATA, FARMESSAGE::DM_SETDLGITEM,
+    FARMESSAGE::DM_SETFOCUS, FARMESSAGE::DM_REDRAW, FARMESSAGE::DM_SETTEXT, FARMESSAGE::DM_SETMAXTEXTLENGTH,
+    FARMESSAGE::DM_SHOWDIALOG, FARMESSAGE::DM_GETFOCUS, FARMESSAGE::DM_GETCURSORPOS,
+    FARMESSAGE::DM_SETCURSORPOS, FARMESSAGE::DM_SETTEXTPTR, FARMESSAGE::DM_SHOWITEM,
+    FARMESSAGE::DM_ADDHISTORY, FARMESSAGE::DM_GETCHECK, FARMESSAGE::DM_SETCHECK, FARMESSAGE::DM_SET3STATE,
+    FARMESSAGE::DM_LISTSORT, FARMESSAGE::DM_LISTGETITEM, FARMESSAGE::DM_LISTGETCURPOS,
+    FARMESSAGE::DM_LISTSETCURPOS, FARMESSAGE::DM_LISTDELETE, FARMESSAGE::DM_LISTADD,
+    FARMESSAGE::DM_LISTADDSTR, FARMESSAGE::DM_LISTUPDATE, FARMESSAGE::DM_LISTINSERT,
+    FARMESSAGE::DM_LISTFINDSTRING, FARMESSAGE::DM_LISTINFO, FARMESSAGE::DM_LISTGETDATA,
+    FARMESSAGE::DM_LISTSETDATA, FARMESSAGE::DM_LISTSETTITLES, FARMESSAGE::DM_LISTGETTITLES,
+    FARMESSAGE::DM_RESIZEDIALOG, FARMESSAGE::DM_SETITEMPOSITION, FARMESSAGE::DM_GETDROPDOWNOPENED,
+    FARMESSAGE::DM_SETDROPDOWNOPENED, FARMESSAGE::DM_SETHISTORY, FARMESSAGE::DM_GETITEMPOSITION,
+    FARMESSAGE::DM_SETINPUTNOTIFY, FARMESSAGE::DM_EDITUNCHANGEDFLAG, FARMESSAGE::DM_GETITEMDATA,
+    FARMESSAGE::DM_SETITEMDATA, FARMESSAGE::DM_LISTSET, FARMESSAGE::DM_GETCURSORSIZE,
+    FARMESSAGE::DM_SETCURSORSIZE, FARMESSAGE::DM_LISTGETDATASIZE, FARMESSAGE::DM_GETSELECTION,
+    FARMESSAGE::DM_SETSELECTION, FARMESSAGE::DM_GETEDITPOSITION, FARMESSAGE::DM_SETEDITPOSITION,
+    FARMESSAGE::DM_SETCOMBOBOXEVENT, FARMESSAGE::DM_GETCOMBOBOXEVENT, FARMESSAGE::DM_GETCONSTTEXTPTR,
+    FARMESSAGE::DM_GETDLGITEMSHORT, FARMESSAGE::DM_SETDLGITEMSHORT, FARMESSAGE::DM_GETDIALOGINFO,
+    FARMESSAGE::DM_GETDIALOGTITLE, FARMESSAGE::DN_FIRST, FARMESSAGE::DN_BTNCLICK,
+    FARMESSAGE::DN_CTLCOLORDIALOG, FARMESSAGE::DN_CTLCOLORDLGITEM, FARMESSAGE::DN_CTLCOLORDLGLIST,
+    FARMESSAGE::DN_DRAWDIALOG, FARMESSAGE::DN_DRAWDLGITEM, FARMESSAGE::DN_EDITCHANGE,
+    FARMESSAGE::DN_ENTERIDLE, FARMESSAGE::DN_GOTFOCUS, FARMESSAGE::DN_HELP, FARMESSAGE::DN_HOTKEY,
+    FARMESSAGE::DN_INITDIALOG, FARMESSAGE::DN_KILLFOCUS, FARMESSAGE::DN_LISTCHANGE, FARMESSAGE::DN_DRAGGED,
+    FARMESSAGE::DN_RESIZECONSOLE, FARMESSAGE::DN_DRAWDIALOGDONE, FARMESSAGE::DN_LISTHOTKEY,
+    FARMESSAGE::DN_INPUT, FARMESSAGE::DN_CONTROLINPUT, FARMESSAGE::DN_CLOSE, FARMESSAGE::DN_GETVALUE,
+    FARMESSAGE::DN_DROPDOWNOPENED, FARMESSAGE::DN_DRAWDLGITEMDONE, FARMESSAGE::DM_USER
+];
+
+fn decode_message(msg: libc::intptr_t) -> Option<ffi::FARMESSAGE> {
+    MESSAGES.iter().cloned().find(|known| *known as libc::intptr_t == msg)
+}
 
-    fn into(self) -> (ffi::FARMESSAGE, libc::intptr_t, *mut libc::c_void) {
+impl<'a> FarMessage<'a> {
+    // Returns None for the messages which are internal to the binding, such messages are passed to
+    // DefDlgProc as is. Messages with the parameters which can't be decoded are returned as Raw.
+    // param2 must be the one passed by Far for the message and stay valid for 'a.
+    unsafe fn from(msg: libc::intptr_t, param1: libc::intptr_t, param2: *mut libc::c_void) -> Option<Self> {
+        match decode_message(msg) {
+            Some(FARMESSAGE::DM_GETDLGDATA) | Some(FARMESSAGE::DM_SETDLGDATA) => None,
+            Some(known) => Some(FarMessage::decode(known, param1, param2).unwrap_or(FarMessage::Raw { msg, param1, param2 })),
+            None => Some(FarMessage::Raw { msg, param1, param2 })
+        }
+    }
+
+    unsafe fn decode(msg: ffi::FARMESSAGE, param1: libc::intptr_t, param2: *mut libc::c_void) -> Option<Self> {
+        let id = param1 as ItemId;
+        let value = param2 as libc::intptr_t;
+        let message = match msg {
+            FARMESSAGE::DM_FIRST => FarMessage::DmFirst,
+            FARMESSAGE::DM_CLOSE => FarMessage::DmClose { exit_code: decode_exit_code(param1)? },
+            FARMESSAGE::DM_ENABLE => FarMessage::DmEnable { id, enable: decode_state(value)? },
+            FARMESSAGE::DM_ENABLEREDRAW => FarMessage::DmEnableRedraw { enable: decode_state(param1)? },
+            FARMESSAGE::DM_GETDLGDATA | FARMESSAGE::DM_SETDLGDATA => return None,
+            FARMESSAGE::DM_GETDLGITEM => FarMessage::DmGetDlgItem { id, item: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_GETDLGRECT => FarMessage::DmGetDlgRect { rect: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_GETTEXT => FarMessage::DmGetText { id, data: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_KEY => FarMessage::DmKey {
+                records: decode_slice(param2 as *const ffi::INPUT_RECORD, decode_index(param1)?)?
+            },
+            FARMESSAGE::DM_MOVEDIALOG => FarMessage::DmMoveDialog {
+                absolute: decode_bool(param1)?,
+                coord: MessageParam::from_raw(param2, true)
+            },
+            FARMESSAGE::DM_SETDLGITEM => {
+                let (item, param) = decode_item(param2, false)?;
+                FarMessage::DmSetDlgItem { id, item, param }
+            },
+            FARMESSAGE::DM_SETFOCUS => FarMessage::DmSetFocus { id },
+            FARMESSAGE::DM_REDRAW => FarMessage::DmRedraw,
+            FARMESSAGE::DM_SETTEXT => {
+                let data = decode_ref::<ffi::FarDialogItemData>(param2)?;
+                FarMessage::DmSetText { id, text: WideString::from(decode_slice(data.ptr_data, data.ptr_length)?) }
+            },
+            FARMESSAGE::DM_SETMAXTEXTLENGTH => FarMessage::DmSetMaxTextLength { id, max_length: value as usize },
+            FARMESSAGE::DM_SHOWDIALOG => FarMessage::DmShowDialog { show: decode_bool(param1)? },
+            FARMESSAGE::DM_GETFOCUS => FarMessage::DmGetFocus,
+            FARMESSAGE::DM_GETCURSORPOS => FarMessage::DmGetCursorPos { id, coord: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_SETCURSORPOS => FarMessage::DmSetCursorPos { id, coord: *decode_ref(param2)? },
+            FARMESSAGE::DM_SETTEXTPTR => FarMessage::DmSetTextPtr { id, text: decode_text(param2 as *const ffi::wchar_t)? },
+            FARMESSAGE::DM_SHOWITEM => FarMessage::DmShowItem { id, show: decode_state(value)? },
+            FARMESSAGE::DM_ADDHISTORY => FarMessage::DmAddHistory { id, text: decode_text(param2 as *const ffi::wchar_t)? },
+            FARMESSAGE::DM_GETCHECK => FarMessage::DmGetCheck { id },
+            FARMESSAGE::DM_SETCHECK => FarMessage::DmSetCheck { id, state: decode_check_state(value)? },
+            FARMESSAGE::DM_SET3STATE => FarMessage::DmSet3State { id, enable: decode_bool(value)? },
+            FARMESSAGE::DM_LISTSORT => FarMessage::DmListSort { id, descending: decode_bool(value)? },
+            FARMESSAGE::DM_LISTGETITEM => FarMessage::DmListGetItem { id, item: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_LISTGETCURPOS => FarMessage::DmListGetCurpos { id, pos: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_LISTSETCURPOS => {
+                let pos = decode_ref::<ffi::FarListPos>(param2)?;
+                FarMessage::DmListSetCurPos {
+                    id,
+                    pos: decode_index(pos.select_pos)?,
+                    top: match pos.top_pos {
+                        -1 => None,
+                        top => Some(decode_index(top)?)
+                    },
+                }
+            },
+            FARMESSAGE::DM_LISTDELETE => FarMessage::DmListDelete {
+                id,
+                items: match decode_ref::<ffi::FarListDelete>(param2) {
+                    Some(delete) => {
+                        let start = decode_index(delete.start_index)?;
+                        Some(start..start + decode_index(delete.count)?)
+                    },
+                    None => None
+                },
+            },
+            FARMESSAGE::DM_LISTADD => FarMessage::DmListAdd { id, items: decode_list(decode_ref(param2)?) },
+            FARMESSAGE::DM_LISTADDSTR => FarMessage::DmListAddStr { id, text: decode_text(param2 as *const ffi::wchar_t)? },
+            FARMESSAGE::DM_LISTUPDATE => {
+                let update = decode_ref::<ffi::FarListUpdate>(param2)?;
+                FarMessage::DmListUpdate { id, index: decode_index(update.index)?, item: ListItem::from_raw(&update.item) }
+            },
+            FARMESSAGE::DM_LISTINSERT => {
+                let insert = decode_ref::<ffi::FarListInsert>(param2)?;
+                FarMessage::DmListInsert { id, index: decode_index(insert.index)?, item: ListItem::from_raw(&insert.item) }
+            },
+            FARMESSAGE::DM_LISTFINDSTRING => {
+                let find = decode_ref::<ffi::FarListFind>(param2)?;
+                FarMessage::DmListFindString {
+                    id,
+                    start: decode_index(find.start_index)?,
+                    pattern: decode_text(find.pattern)?,
+                    flags: find.flags,
+                }
+            },
+            FARMESSAGE::DM_LISTINFO => FarMessage::DmListInfo { id, info: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_LISTGETDATA => FarMessage::DmListGetData { id, index: decode_index(value)? },
+            FARMESSAGE::DM_LISTSETDATA => {
+                let data = decode_ref::<ffi::FarListItemData>(param2)?;
+                FarMessage::DmListSetData {
+                    id,
+                    index: decode_index(data.index)?,
+                    data: decode_slice(data.data as *const u8, data.data_size)?,
+                }
+            },
+            FARMESSAGE::DM_LISTSETTITLES => {
+                let titles = decode_ref::<ffi::FarListTitles>(param2)?;
+                FarMessage::DmListSetTitles { id, title: decode_text(titles.title), bottom: decode_text(titles.bottom) }
+            },
+            FARMESSAGE::DM_LISTGETTITLES => FarMessage::DmListGetTitles { id, titles: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_RESIZEDIALOG => FarMessage::DmResizeDialog { size: *decode_ref(param2)? },
+            FARMESSAGE::DM_SETITEMPOSITION => {
+                let rect = decode_ref::<SMALL_RECT>(param2)?;
+                FarMessage::DmSetItemPosition {
+                    id,
+                    rect: ItemRect {
+                        left: rect.Left as isize,
+                        top: rect.Top as isize,
+                        right: rect.Right as isize,
+                        bottom: rect.Bottom as isize,
+                    },
+                }
+            },
+            FARMESSAGE::DM_GETDROPDOWNOPENED => FarMessage::DmGetDropDownOpened { id },
+            FARMESSAGE::DM_SETDROPDOWNOPENED => FarMessage::DmSetDropdownOpened { id, open: decode_bool(value)? },
+            FARMESSAGE::DM_SETHISTORY => FarMessage::DmSetHistory { id, history: decode_text(param2 as *const ffi::wchar_t) },
+            FARMESSAGE::DM_GETITEMPOSITION => FarMessage::DmGetItemPosition { id, rect: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_SETINPUTNOTIFY => FarMessage::DmSetInputNotify { notify: decode_state(param1)? },
+            /* FARMESSAGE::DM_SETMOUSEEVENTNOTIFY          = FARMESSAGE::DM_SETINPUTNOTIFY,*/
+            FARMESSAGE::DM_EDITUNCHANGEDFLAG => FarMessage::DmEditUnchangedFlag { id, unchanged: decode_state(value)? },
+            FARMESSAGE::DM_GETITEMDATA => FarMessage::DmGetItemData { id },
+            FARMESSAGE::DM_SETITEMDATA => FarMessage::DmSetItemData { id, data: value },
+            FARMESSAGE::DM_LISTSET => FarMessage::DmListSet { id, items: decode_list(decode_ref(param2)?) },
+            FARMESSAGE::DM_GETCURSORSIZE => FarMessage::DmGetCursorSize { id },
+            FARMESSAGE::DM_SETCURSORSIZE => {
+                FarMessage::DmSetCursorSize { id, visible: decode_bool(value & 0xFFFF)?, size: (value >> 16) as usize }
+            },
+            FARMESSAGE::DM_LISTGETDATASIZE => FarMessage::DmListGetDataSize { id, index: decode_index(value)? },
+            FARMESSAGE::DM_GETSELECTION => FarMessage::DmGetSelection { id, selection: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_SETSELECTION => FarMessage::DmSetSelection { id, selection: MessageParam::from_raw(param2, false) },
+            FARMESSAGE::DM_GETEDITPOSITION => FarMessage::DmGetEditPosition { id, position: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_SETEDITPOSITION => FarMessage::DmSetEditPosition { id, position: MessageParam::from_raw(param2, false) },
+            FARMESSAGE::DM_SETCOMBOBOXEVENT => FarMessage::DmSetComboboxEvent { id, events: COMBOBOXEVENTTYPE::from_bits(value)? },
+            FARMESSAGE::DM_GETCOMBOBOXEVENT => FarMessage::DmGetComboboxEvent { id },
+            FARMESSAGE::DM_GETCONSTTEXTPTR => FarMessage::DmGetConstTextPtr { id },
+            FARMESSAGE::DM_GETDLGITEMSHORT => FarMessage::DmGetDlgItemShort { id, item: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_SETDLGITEMSHORT => {
+                let (item, param) = decode_item(param2, false)?;
+                FarMessage::DmSetDlgItemShort { id, item, param }
+            },
+            FARMESSAGE::DM_GETDIALOGINFO => FarMessage::DmGetDialogInfo { info: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DM_GETDIALOGTITLE => FarMessage::DmGetDialogTitle { data: MessageParam::from_raw(param2, true) },
+            FARMESSAGE::DN_FIRST => FarMessage::DnFirst,
+            FARMESSAGE::DN_BTNCLICK => FarMessage::DnBtnClick { id, state: DialogItemSelection { value: value as u8 } },
+            FARMESSAGE::DN_CTLCOLORDIALOG => FarMessage::DnCtlColorDialog { color: decode_mut(param2)? },
+            FARMESSAGE::DN_CTLCOLORDLGITEM => {
+                let (flags, colors) = decode_colors(param2)?;
+                FarMessage::DnCtlColorDlgItem { id, flags, colors }
+            },
+            FARMESSAGE::DN_CTLCOLORDLGLIST => {
+                let (flags, colors) = decode_colors(param2)?;
+                FarMessage::DnCtlColorDlgList { id, flags, colors }
+            },
+            FARMESSAGE::DN_DRAWDIALOG => FarMessage::DnDrawDialog,
+            FARMESSAGE::DN_DRAWDLGITEM => {
+                let (item, param) = decode_item(param2, true)?;
+                FarMessage::DnDrawDlgItem { id, item, param }
+            },
+            FARMESSAGE::DN_EDITCHANGE => {
+                let (item, param) = decode_item(param2, true)?;
+                FarMessage::DnEditChange { id, item, param }
+            },
+            FARMESSAGE::DN_ENTERIDLE => FarMessage::DnEnterIdle,
+            FARMESSAGE::DN_GOTFOCUS => FarMessage::DnGotFocus { id },
+            FARMESSAGE::DN_HELP => FarMessage::DnHelp {
+                id,
+                topic: decode_text(param2 as *const ffi::wchar_t),
+                param: MessageParam::from_raw(param2, false)
+            },
+            FARMESSAGE::DN_HOTKEY => FarMessage::DnHotKey { id, event: InputEvent::from(decode_ref(param2)?)? },
+            // param2 is the dialog itself, which is already borrowed by dlg_proc
+            FARMESSAGE::DN_INITDIALOG => FarMessage::DnInitDialog { focus: id },
+            FARMESSAGE::DN_KILLFOCUS => FarMessage::DnKillFocus { id },
+            FARMESSAGE::DN_LISTCHANGE => FarMessage::DnListChange { id, pos: decode_index(value)? },
+            FARMESSAGE::DN_DRAGGED => FarMessage::DnDragged { stage: param1, cancelled: decode_bool(value)? },
+            FARMESSAGE::DN_RESIZECONSOLE => FarMessage::DnResizeConsole { size: *decode_ref(param2)? },
+            FARMESSAGE::DN_DRAWDIALOGDONE => FarMessage::DnDrawDialogDone,
+            FARMESSAGE::DN_LISTHOTKEY => FarMessage::DnListHotKey { id, pos: decode_index(value)? },
+            FARMESSAGE::DN_INPUT => FarMessage::DnInput { event: InputEvent::from(decode_ref(param2)?)? },
+            FARMESSAGE::DN_CONTROLINPUT => FarMessage::DnControlInput { id, event: InputEvent::from(decode_ref(param2)?)? },
+            FARMESSAGE::DN_CLOSE => FarMessage::DnClose { id: decode_exit_code(param1)? },
+            FARMESSAGE::DN_GETVALUE => FarMessage::DnGetValue { id, value: decode_mut(param2)? },
+            FARMESSAGE::DN_DROPDOWNOPENED => FarMessage::DnDropdownOpened { id, opened: decode_bool(value)? },
+            FARMESSAGE::DN_DRAWDLGITEMDONE => {
+                let (item, param) = decode_item(param2, true)?;
+                FarMessage::DnDrawDlgItemDone { id, item, param }
+            },
+            FARMESSAGE::DM_USER => FarMessage::DmUser { param1, param2 },
+        };
+        Some(message)
+    }
+
+    // Encodes the message and passes it to send, the parameters are valid only during the call.
+    fn with_raw<R, S>(self, send: S) -> R where S: FnOnce(libc::intptr_t, libc::intptr_t, *mut libc::c_void) -> R {
+        let null = ptr::null_mut();
+        let value = |value: libc::intptr_t| value as *mut libc::c_void;
         match self {
-            FarMessage::DmFirst { param1, param2 } => (ffi::FARMESSAGE::DM_FIRST, param1, param2),
-            FarMessage::DmClose { param1, param2 } => (ffi::FARMESSAGE::DM_CLOSE, param1, param2),
-            FarMessage::DmEnable { param1, param2 } => (ffi::FARMESSAGE::DM_ENABLE, param1, param2),
-            FarMessage::DmEnableRedraw { param1, param2 } => (ffi::FARMESSAGE::DM_ENABLEREDRAW, param1, param2),
-            FarMessage::DmGetDlgItem { param1, param2 } => (ffi::FARMESSAGE::DM_GETDLGITEM, param1, param2),
-            FarMessage::DmGetDlgRect { param1, param2 } => (ffi::FARMESSAGE::DM_GETDLGRECT, param1, param2),
-            FarMessage::DmGetText { param1, param2 } => (ffi::FARMESSAGE::DM_GETTEXT, param1, param2),
-            FarMessage::DmKey { param1, param2 } => (ffi::FARMESSAGE::DM_KEY, param1, param2),
-            FarMessage::DmMoveDialog { param1, param2 } => (ffi::FARMESSAGE::DM_MOVEDIALOG, param1, param2),
-            FarMessage::DmSetDlgItem { param1, param2 } => (ffi::FARMESSAGE::DM_SETDLGITEM, param1, param2),
-            FarMessage::DmSetFocus { param1, param2 } => (ffi::FARMESSAGE::DM_SETFOCUS, param1, param2),
-            FarMessage::DmRedraw { param1, param2 } => (ffi::FARMESSAGE::DM_REDRAW, param1, param2),
-            FarMessage::DmSetText { param1, param2 } => (ffi::FARMESSAGE::DM_SETTEXT, param1, param2),
-            FarMessage::DmSetMaxTextLength { param1, param2 } => (ffi::FARMESSAGE::DM_SETMAXTEXTLENGTH, param1, param2),
-            FarMessage::DmShowDialog { param1, param2 } => (ffi::FARMESSAGE::DM_SHOWDIALOG, param1, param2),
-            FarMessage::DmGetFocus { param1, param2 } => (ffi::FARMESSAGE::DM_GETFOCUS, param1, param2),
-            FarMessage::DmGetCursorPos { param1, param2 } => (ffi::FARMESSAGE::DM_GETCURSORPOS, param1, param2),
-            FarMessage::DmSetCursorPos { param1, param2 } => (ffi::FARMESSAGE::DM_SETCURSORPOS, param1, param2),
-            FarMessage::DmSetTextPtr { param1, param2 } => (ffi::FARMESSAGE::DM_SETTEXTPTR, param1, param2),
-            FarMessage::DmShowItem { param1, param2 } => (ffi::FARMESSAGE::DM_SHOWITEM, param1, param2),
-            FarMessage::DmAddHistory { param1, param2 } => (ffi::FARMESSAGE::DM_ADDHISTORY, param1, param2),
-            FarMessage::DmGetCheck { param1, param2 } => (ffi::FARMESSAGE::DM_GETCHECK, param1, param2),
-            FarMessage::DmSetCheck { param1, param2 } => (ffi::FARMESSAGE::DM_SETCHECK, param1, param2),
-            FarMessage::DmSet3State { param1, param2 } => (ffi::FARMESSAGE::DM_SET3STATE, param1, param2),
-            FarMessage::DmListSort { param1, param2 } => (ffi::FARMESSAGE::DM_LISTSORT, param1, param2),
-            FarMessage::DmListGetItem { param1, param2 } => (ffi::FARMESSAGE::DM_LISTGETITEM, param1, param2),
-            FarMessage::DmListGetCurpos { param1, param2 } => (ffi::FARMESSAGE::DM_LISTGETCURPOS, param1, param2),
-            FarMessage::DmListSetCurPos { param1, param2 } => (ffi::FARMESSAGE::DM_LISTSETCURPOS, param1, param2),
-            FarMessage::DmListDelete { param1, param2 } => (ffi::FARMESSAGE::DM_LISTDELETE, param1, param2),
-            FarMessage::DmListAdd { param1, param2 } => (ffi::FARMESSAGE::DM_LISTADD, param1, param2),
-            FarMessage::DmListAddStr { param1, param2 } => (ffi::FARMESSAGE::DM_LISTADDSTR, param1, param2),
-            FarMessage::DmListUpdate { param1, param2 } => (ffi::FARMESSAGE::DM_LISTUPDATE, param1, param2),
-            FarMessage::DmListInsert { param1, param2 } => (ffi::FARMESSAGE::DM_LISTINSERT, param1, param2),
-            FarMessage::DmListFindString { param1, param2 } => (ffi::FARMESSAGE::DM_LISTFINDSTRING, param1, param2),
-            FarMessage::DmListInfo { param1, param2 } => (ffi::FARMESSAGE::DM_LISTINFO, param1, param2),
-            FarMessage::DmListGetData { param1, param2 } => (ffi::FARMESSAGE::DM_LISTGETDATA, param1, param2),
-            FarMessage::DmListSetData { param1, param2 } => (ffi::FARMESSAGE::DM_LISTSETDATA, param1, param2),
-            FarMessage::DmListSetTitles { param1, param2 } => (ffi::FARMESSAGE::DM_LISTSETTITLES, param1, param2),
-            FarMessage::DmListGetTitles { param1, param2 } => (ffi::FARMESSAGE::DM_LISTGETTITLES, param1, param2),
-            FarMessage::DmResizeDialog { param1, param2 } => (ffi::FARMESSAGE::DM_RESIZEDIALOG, param1, param2),
-            FarMessage::DmSetItemPosition { param1, param2 } => (ffi::FARMESSAGE::DM_SETITEMPOSITION, param1, param2),
-            FarMessage::DmGetDropDownOpened { param1, param2 } => (ffi::FARMESSAGE::DM_GETDROPDOWNOPENED, param1, param2),
-            FarMessage::DmSetDropdownOpened { param1, param2 } => (ffi::FARMESSAGE::DM_SETDROPDOWNOPENED, param1, param2),
-            FarMessage::DmSetHistory { param1, param2 } => (ffi::FARMESSAGE::DM_SETHISTORY, param1, param2),
-            FarMessage::DmGetItemPosition { param1, param2 } => (ffi::FARMESSAGE::DM_GETITEMPOSITION, param1, param2),
-            FarMessage::DmSetInputNotify { param1, param2 } => (ffi::FARMESSAGE::DM_SETINPUTNOTIFY, param1, param2),
-            FarMessage::DmEditUnchangedFlag { param1, param2 } => (ffi::FARMESSAGE::DM_EDITUNCHANGEDFLAG, param1, param2),
-            FarMessage::DmGetItemData { param1, param2 } => (ffi::FARMESSAGE::DM_GETITEMDATA, param1, param2),
-            FarMessage::DmSetItemData { param1, param2 } => (ffi::FARMESSAGE::DM_SETITEMDATA, param1, param2),
-            FarMessage::DmListSet { param1, param2 } => (ffi::FARMESSAGE::DM_LISTSET, param1, param2),
-            FarMessage::DmGetCursorSize { param1, param2 } => (ffi::FARMESSAGE::DM_GETCURSORSIZE, param1, param2),
-            FarMessage::DmSetCursorSize { param1, param2 } => (ffi::FARMESSAGE::DM_SETCURSORSIZE, param1, param2),
-            FarMessage::DmListGetDataSize { param1, param2 } => (ffi::FARMESSAGE::DM_LISTGETDATASIZE, param1, param2),
-            FarMessage::DmGetSelection { param1, param2 } => (ffi::FARMESSAGE::DM_GETSELECTION, param1, param2),
-            FarMessage::DmSetSelection { param1, param2 } => (ffi::FARMESSAGE::DM_SETSELECTION, param1, param2),
-            FarMessage::DmGetEditPosition { param1, param2 } => (ffi::FARMESSAGE::DM_GETEDITPOSITION, param1, param2),
-            FarMessage::DmSetEditPosition { param1, param2 } => (ffi::FARMESSAGE::DM_SETEDITPOSITION, param1, param2),
-            FarMessage::DmSetComboboxEvent { param1, param2 } => (ffi::FARMESSAGE::DM_SETCOMBOBOXEVENT, param1, param2),
-            FarMessage::DmGetComboboxEvent { param1, param2 } => (ffi::FARMESSAGE::DM_GETCOMBOBOXEVENT, param1, param2),
-            FarMessage::DmGetConstTextPtr { param1, param2 } => (ffi::FARMESSAGE::DM_GETCONSTTEXTPTR, param1, param2),
-            FarMessage::DmGetDlgItemShort { param1, param2 } => (ffi::FARMESSAGE::DM_GETDLGITEMSHORT, param1, param2),
-            FarMessage::DmSetDlgItemShort { param1, param2 } => (ffi::FARMESSAGE::DM_SETDLGITEMSHORT, param1, param2),
-            FarMessage::DmGetDialogInfo { param1, param2 } => (ffi::FARMESSAGE::DM_GETDIALOGINFO, param1, param2),
-            FarMessage::DmGetDialogTitle { param1, param2 } => (ffi::FARMESSAGE::DM_GETDIALOGTITLE, param1, param2),
-            FarMessage::DnFirst { param1, param2 } => (ffi::FARMESSAGE::DN_FIRST, param1, param2),
-            FarMessage::DnBtnClick { id, state } => (ffi::FARMESSAGE::DN_BTNCLICK, id, state.value as *mut libc::c_void),
-            FarMessage::DnCtlColorDialog { param1, param2 } => (ffi::FARMESSAGE::DN_CTLCOLORDIALOG, param1, param2),
-            FarMessage::DnCtlColorDlgItem { param1, param2 } => (ffi::FARMESSAGE::DN_CTLCOLORDLGITEM, param1, param2),
-            FarMessage::DnCtlColorDlgList { param1, param2 } => (ffi::FARMESSAGE::DN_CTLCOLORDLGLIST, param1, param2),
-            FarMessage::DnDrawDialog { param1, param2 } => (ffi::FARMESSAGE::DN_DRAWDIALOG, param1, param2),
-            FarMessage::DnDrawDlgItem { param1, param2 } => (ffi::FARMESSAGE::DN_DRAWDLGITEM, param1, param2),
-            FarMessage::DnEditChange { param1, param2 } => (ffi::FARMESSAGE::DN_EDITCHANGE, param1, param2),
-            FarMessage::DnEnterIdle { param1, param2 } => (ffi::FARMESSAGE::DN_ENTERIDLE, param1, param2),
-            FarMessage::DnGotFocus { param1, param2 } => (ffi::FARMESSAGE::DN_GOTFOCUS, param1, param2),
-            FarMessage::DnHelp { param1, param2 } => (ffi::FARMESSAGE::DN_HELP, param1, param2),
-            FarMessage::DnHotKey { param1, param2 } => (ffi::FARMESSAGE::DN_HOTKEY, param1, param2),
-            FarMessage::DnInitDialog { param1, param2 } => (ffi::FARMESSAGE::DN_INITDIALOG, param1, param2),
-            FarMessage::DnKillFocus { param1, param2 } => (ffi::FARMESSAGE::DN_KILLFOCUS, param1, param2),
-            FarMessage::DnListChange { param1, param2 } => (ffi::FARMESSAGE::DN_LISTCHANGE, param1, param2),
-            FarMessage::DnDragged { param1, param2 } => (ffi::FARMESSAGE::DN_DRAGGED, param1, param2),
-            FarMessage::DnResizeConsole { param1, param2 } => (ffi::FARMESSAGE::DN_RESIZECONSOLE, param1, param2),
-            FarMessage::DnDrawDialogDone { param1, param2 } => (ffi::FARMESSAGE::DN_DRAWDIALOGDONE, param1, param2),
-            FarMessage::DnListHotKey { param1, param2 } => (ffi::FARMESSAGE::DN_LISTHOTKEY, param1, param2),
-            FarMessage::DnInput { param1, param2 } => (ffi::FARMESSAGE::DN_INPUT, param1, param2),
-            FarMessage::DnControlInput { param1, param2 } => (ffi::FARMESSAGE::DN_CONTROLINPUT, param1, param2),
-            FarMessage::DnClose { param1, param2 } => (ffi::FARMESSAGE::DN_CLOSE, param1, param2),
-            FarMessage::DnGetValue { param1, param2 } => (ffi::FARMESSAGE::DN_GETVALUE, param1, param2),
-            FarMessage::DnDropdownOpened { param1, param2 } => (ffi::FARMESSAGE::DN_DROPDOWNOPENED, param1, param2),
-            FarMessage::DnDrawDlgItemDone { param1, param2 } => (ffi::FARMESSAGE::DN_DRAWDLGITEMDONE, param1, param2),
-            FarMessage::DmUser { param1, param2 } => (ffi::FARMESSAGE::DM_USER, param1, param2),
+            FarMessage::DmFirst => send(ffi::FARMESSAGE::DM_FIRST as libc::intptr_t, 0, null),
+            FarMessage::DmClose { exit_code } => send(ffi::FARMESSAGE::DM_CLOSE as libc::intptr_t, encode_exit_code(exit_code), null),
+            FarMessage::DmEnable { id, enable } => send(ffi::FARMESSAGE::DM_ENABLE as libc::intptr_t, id as libc::intptr_t, value(encode_state(enable))),
+            FarMessage::DmEnableRedraw { enable } => send(ffi::FARMESSAGE::DM_ENABLEREDRAW as libc::intptr_t, encode_state(enable), null),
+            FarMessage::DmGetDlgItem { id, item } => send(ffi::FARMESSAGE::DM_GETDLGITEM as libc::intptr_t, id as libc::intptr_t, item.as_raw()),
+            FarMessage::DmGetDlgRect { rect } => send(ffi::FARMESSAGE::DM_GETDLGRECT as libc::intptr_t, 0, rect.as_raw()),
+            FarMessage::DmGetText { id, data } => send(ffi::FARMESSAGE::DM_GETTEXT as libc::intptr_t, id as libc::intptr_t, data.as_raw()),
+            FarMessage::DmKey { records } => {
+                send(ffi::FARMESSAGE::DM_KEY as libc::intptr_t, records.len() as libc::intptr_t, records.as_ptr() as *mut libc::c_void)
+            },
+            FarMessage::DmMoveDialog { absolute, coord } => send(ffi::FARMESSAGE::DM_MOVEDIALOG as libc::intptr_t, absolute as libc::intptr_t, coord.as_raw()),
+            FarMessage::DmSetDlgItem { id, item, param } => {
+                encode_item(item, param, |item| send(ffi::FARMESSAGE::DM_SETDLGITEM as libc::intptr_t, id as libc::intptr_t, item))
+            },
+            FarMessage::DmSetFocus { id } => send(ffi::FARMESSAGE::DM_SETFOCUS as libc::intptr_t, id as libc::intptr_t, null),
+            FarMessage::DmRedraw => send(ffi::FARMESSAGE::DM_REDRAW as libc::intptr_t, 0, null),
+            FarMessage::DmSetText { id, text } => {
+                let mut data = ffi::FarDialogItemData {
+                    struct_size: mem::size_of::<ffi::FarDialogItemData>(),
+                    ptr_length: text.len(),
+                    ptr_data: text.as_ptr() as *mut ffi::wchar_t,
+                };
+                send(ffi::FARMESSAGE::DM_SETTEXT as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut data))
+            },
+            FarMessage::DmSetMaxTextLength { id, max_length } => send(ffi::FARMESSAGE::DM_SETMAXTEXTLENGTH as libc::intptr_t, id as libc::intptr_t, value(max_length as libc::intptr_t)),
+            FarMessage::DmShowDialog { show } => send(ffi::FARMESSAGE::DM_SHOWDIALOG as libc::intptr_t, show as libc::intptr_t, null),
+            FarMessage::DmGetFocus => send(ffi::FARMESSAGE::DM_GETFOCUS as libc::intptr_t, 0, null),
+            FarMessage::DmGetCursorPos { id, coord } => send(ffi::FARMESSAGE::DM_GETCURSORPOS as libc::intptr_t, id as libc::intptr_t, coord.as_raw()),
+            FarMessage::DmSetCursorPos { id, mut coord } => send(ffi::FARMESSAGE::DM_SETCURSORPOS as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut coord)),
+            FarMessage::DmSetTextPtr { id, text } => send(ffi::FARMESSAGE::DM_SETTEXTPTR as libc::intptr_t, id as libc::intptr_t, encode_text(&text)),
+            FarMessage::DmShowItem { id, show } => send(ffi::FARMESSAGE::DM_SHOWITEM as libc::intptr_t, id as libc::intptr_t, value(encode_state(show))),
+            FarMessage::DmAddHistory { id, text } => send(ffi::FARMESSAGE::DM_ADDHISTORY as libc::intptr_t, id as libc::intptr_t, encode_text(&text)),
+            FarMessage::DmGetCheck { id } => send(ffi::FARMESSAGE::DM_GETCHECK as libc::intptr_t, id as libc::intptr_t, null),
+            FarMessage::DmSetCheck { id, state } => send(ffi::FARMESSAGE::DM_SETCHECK as libc::intptr_t, id as libc::intptr_t, value(state as libc::intptr_t)),
+            FarMessage::DmSet3State { id, enable } => send(ffi::FARMESSAGE::DM_SET3STATE as libc::intptr_t, id as libc::intptr_t, value(enable as libc::intptr_t)),
+            FarMessage::DmListSort { id, descending } => send(ffi::FARMESSAGE::DM_LISTSORT as libc::intptr_t, id as libc::intptr_t, value(descending as libc::intptr_t)),
+            FarMessage::DmListGetItem { id, item } => send(ffi::FARMESSAGE::DM_LISTGETITEM as libc::intptr_t, id as libc::intptr_t, item.as_raw()),
+            FarMessage::DmListGetCurpos { id, pos } => send(ffi::FARMESSAGE::DM_LISTGETCURPOS as libc::intptr_t, id as libc::intptr_t, pos.as_raw()),
+            FarMessage::DmListSetCurPos { id, pos, top } => {
+                let mut list_pos = ffi::FarListPos {
+                    struct_size: mem::size_of::<ffi::FarListPos>(),
+                    select_pos: pos as libc::intptr_t,
+                    top_pos: top.map_or(-1, |top| top as libc::intptr_t),
+                };
+                send(ffi::FARMESSAGE::DM_LISTSETCURPOS as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut list_pos))
+            },
+            FarMessage::DmListDelete { id, items } => match items {
+                Some(items) => {
+                    let mut delete = ffi::FarListDelete {
+                        struct_size: mem::size_of::<ffi::FarListDelete>(),
+                        start_index: items.start as libc::intptr_t,
+                        count: items.len() as libc::intptr_t,
+                    };
+                    send(ffi::FARMESSAGE::DM_LISTDELETE as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut delete))
+                },
+                None => send(ffi::FARMESSAGE::DM_LISTDELETE as libc::intptr_t, id as libc::intptr_t, null)
+            },
+            FarMessage::DmListAdd { id, items } => {
+                let mut list = RawList::new(&items);
+                send(ffi::FARMESSAGE::DM_LISTADD as libc::intptr_t, id as libc::intptr_t, list.as_ptr() as *mut libc::c_void)
+            },
+            FarMessage::DmListAddStr { id, text } => send(ffi::FARMESSAGE::DM_LISTADDSTR as libc::intptr_t, id as libc::intptr_t, encode_text(&text)),
+            FarMessage::DmListUpdate { id, index, item } => {
+                let mut update = ffi::FarListUpdate {
+                    struct_size: mem::size_of::<ffi::FarListUpdate>(),
+                    index: index as libc::intptr_t,
+                    item: item.as_raw(),
+                };
+                send(ffi::FARMESSAGE::DM_LISTUPDATE as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut update))
+            },
+            FarMessage::DmListInsert { id, index, item } => {
+                let mut insert = ffi::FarListInsert {
+                    struct_size: mem::size_of::<ffi::FarListInsert>(),
+                    index: index as libc::intptr_t,
+                    item: item.as_raw(),
+                };
+                send(ffi::FARMESSAGE::DM_LISTINSERT as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut insert))
+            },
+            FarMessage::DmListFindString { id, start, pattern, flags } => {
+                let mut find = ffi::FarListFind {
+                    struct_size: mem::size_of::<ffi::FarListFind>(),
+                    start_index: start as libc::intptr_t,
+                    pattern: pattern.as_ptr(),
+                    flags,
+                };
+                send(ffi::FARMESSAGE::DM_LISTFINDSTRING as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut find))
+            },
+            FarMessage::DmListInfo { id, info } => send(ffi::FARMESSAGE::DM_LISTINFO as libc::intptr_t, id as libc::intptr_t, info.as_raw()),
+            FarMessage::DmListGetData { id, index } => send(ffi::FARMESSAGE::DM_LISTGETDATA as libc::intptr_t, id as libc::intptr_t, value(index as libc::intptr_t)),
+            FarMessage::DmListSetData { id, index, data } => {
+                let mut item_data = ffi::FarListItemData {
+                    struct_size: mem::size_of::<ffi::FarListItemData>(),
+                    index: index as libc::intptr_t,
+                    data_size: data.len(),
+                    data: data.as_ptr() as *mut libc::c_void,
+                };
+                send(ffi::FARMESSAGE::DM_LISTSETDATA as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut item_data))
+            },
+            FarMessage::DmListSetTitles { id, title, bottom } => {
+                let mut titles = ffi::FarListTitles {
+                    struct_size: mem::size_of::<ffi::FarListTitles>(),
+                    title_size: title.as_ref().map_or(0, |title| title.len() + 1),
+                    title: title.as_ref().map_or(ptr::null(), |title| title.as_ptr()),
+                    bottom_size: bottom.as_ref().map_or(0, |bottom| bottom.len() + 1),
+                    bottom: bottom.as_ref().map_or(ptr::null(), |bottom| bottom.as_ptr()),
+                };
+                send(ffi::FARMESSAGE::DM_LISTSETTITLES as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut titles))
+            },
+            FarMessage::DmListGetTitles { id, titles } => send(ffi::FARMESSAGE::DM_LISTGETTITLES as libc::intptr_t, id as libc::intptr_t, titles.as_raw()),
+            FarMessage::DmResizeDialog { mut size } => send(ffi::FARMESSAGE::DM_RESIZEDIALOG as libc::intptr_t, 0, encode_ptr(&mut size)),
+            FarMessage::DmSetItemPosition { id, rect } => {
+                let mut rect = SMALL_RECT {
+                    Left: rect.left as i16,
+                    Top: rect.top as i16,
+                    Right: rect.right as i16,
+                    Bottom: rect.bottom as i16,
+                };
+                send(ffi::FARMESSAGE::DM_SETITEMPOSITION as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut rect))
+            },
+            FarMessage::DmGetDropDownOpened { id } => send(ffi::FARMESSAGE::DM_GETDROPDOWNOPENED as libc::intptr_t, id as libc::intptr_t, null),
+            FarMessage::DmSetDropdownOpened { id, open } => send(ffi::FARMESSAGE::DM_SETDROPDOWNOPENED as libc::intptr_t, id as libc::intptr_t, value(open as libc::intptr_t)),
+            FarMessage::DmSetHistory { id, history } => {
+                send(ffi::FARMESSAGE::DM_SETHISTORY as libc::intptr_t, id as libc::intptr_t, history.as_ref().map_or(null, encode_text))
+            },
+            FarMessage::DmGetItemPosition { id, rect } => send(ffi::FARMESSAGE::DM_GETITEMPOSITION as libc::intptr_t, id as libc::intptr_t, rect.as_raw()),
+            FarMessage::DmSetInputNotify { notify } => send(ffi::FARMESSAGE::DM_SETINPUTNOTIFY as libc::intptr_t, encode_state(notify), null),
+            FarMessage::DmEditUnchangedFlag { id, unchanged } => send(ffi::FARMESSAGE::DM_EDITUNCHANGEDFLAG as libc::intptr_t, id as libc::intptr_t, value(encode_state(unchanged))),
+            FarMessage::DmGetItemData { id } => send(ffi::FARMESSAGE::DM_GETITEMDATA as libc::intptr_t, id as libc::intptr_t, null),
+            FarMessage::DmSetItemData { id, data } => send(ffi::FARMESSAGE::DM_SETITEMDATA as libc::intptr_t, id as libc::intptr_t, value(data)),
+            FarMessage::DmListSet { id, items } => {
+                let mut list = RawList::new(&items);
+                send(ffi::FARMESSAGE::DM_LISTSET as libc::intptr_t, id as libc::intptr_t, list.as_ptr() as *mut libc::c_void)
+            },
+            FarMessage::DmGetCursorSize { id } => send(ffi::FARMESSAGE::DM_GETCURSORSIZE as libc::intptr_t, id as libc::intptr_t, null),
+            FarMessage::DmSetCursorSize { id, visible, size } => {
+                send(ffi::FARMESSAGE::DM_SETCURSORSIZE as libc::intptr_t, id as libc::intptr_t, value(((size as libc::intptr_t) << 16) | visible as libc::intptr_t))
+            },
+            FarMessage::DmListGetDataSize { id, index } => send(ffi::FARMESSAGE::DM_LISTGETDATASIZE as libc::intptr_t, id as libc::intptr_t, value(index as libc::intptr_t)),
+            FarMessage::DmGetSelection { id, selection } => send(ffi::FARMESSAGE::DM_GETSELECTION as libc::intptr_t, id as libc::intptr_t, selection.as_raw()),
+            FarMessage::DmSetSelection { id, selection } => send(ffi::FARMESSAGE::DM_SETSELECTION as libc::intptr_t, id as libc::intptr_t, selection.as_raw()),
+            FarMessage::DmGetEditPosition { id, position } => send(ffi::FARMESSAGE::DM_GETEDITPOSITION as libc::intptr_t, id as libc::intptr_t, position.as_raw()),
+            FarMessage::DmSetEditPosition { id, position } => send(ffi::FARMESSAGE::DM_SETEDITPOSITION as libc::intptr_t, id as libc::intptr_t, position.as_raw()),
+            FarMessage::DmSetComboboxEvent { id, events } => send(ffi::FARMESSAGE::DM_SETCOMBOBOXEVENT as libc::intptr_t, id as libc::intptr_t, value(events.bits())),
+            FarMessage::DmGetComboboxEvent { id } => send(ffi::FARMESSAGE::DM_GETCOMBOBOXEVENT as libc::intptr_t, id as libc::intptr_t, null),
+            FarMessage::DmGetConstTextPtr { id } => send(ffi::FARMESSAGE::DM_GETCONSTTEXTPTR as libc::intptr_t, id as libc::intptr_t, null),
+            FarMessage::DmGetDlgItemShort { id, item } => send(ffi::FARMESSAGE::DM_GETDLGITEMSHORT as libc::intptr_t, id as libc::intptr_t, item.as_raw()),
+            FarMessage::DmSetDlgItemShort { id, item, param } => {
+                encode_item(item, param, |item| send(ffi::FARMESSAGE::DM_SETDLGITEMSHORT as libc::intptr_t, id as libc::intptr_t, item))
+            },
+            FarMessage::DmGetDialogInfo { info } => send(ffi::FARMESSAGE::DM_GETDIALOGINFO as libc::intptr_t, 0, info.as_raw()),
+            FarMessage::DmGetDialogTitle { data } => send(ffi::FARMESSAGE::DM_GETDIALOGTITLE as libc::intptr_t, 0, data.as_raw()),
+            FarMessage::DnFirst => send(ffi::FARMESSAGE::DN_FIRST as libc::intptr_t, 0, null),
+            FarMessage::DnBtnClick { id, state } => send(ffi::FARMESSAGE::DN_BTNCLICK as libc::intptr_t, id as libc::intptr_t, value(state.value as libc::intptr_t)),
+            FarMessage::DnCtlColorDialog { color } => send(ffi::FARMESSAGE::DN_CTLCOLORDIALOG as libc::intptr_t, 0, encode_ptr(color)),
+            FarMessage::DnCtlColorDlgItem { id, flags, colors } => {
+                let mut colors = ffi::FarDialogItemColors {
+                    struct_size: mem::size_of::<ffi::FarDialogItemColors>(),
+                    flags,
+                    colors_count: colors.len(),
+                    colors: colors.as_mut_ptr(),
+                };
+                send(ffi::FARMESSAGE::DN_CTLCOLORDLGITEM as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut colors))
+            },
+            FarMessage::DnCtlColorDlgList { id, flags, colors } => {
+                let mut colors = ffi::FarDialogItemColors {
+                    struct_size: mem::size_of::<ffi::FarDialogItemColors>(),
+                    flags,
+                    colors_count: colors.len(),
+                    colors: colors.as_mut_ptr(),
+                };
+                send(ffi::FARMESSAGE::DN_CTLCOLORDLGLIST as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut colors))
+            },
+            FarMessage::DnDrawDialog => send(ffi::FARMESSAGE::DN_DRAWDIALOG as libc::intptr_t, 0, null),
+            FarMessage::DnDrawDlgItem { id, item, param } => {
+                encode_item(item, param, |item| send(ffi::FARMESSAGE::DN_DRAWDLGITEM as libc::intptr_t, id as libc::intptr_t, item))
+            },
+            FarMessage::DnEditChange { id, item, param } => {
+                encode_item(item, param, |item| send(ffi::FARMESSAGE::DN_EDITCHANGE as libc::intptr_t, id as libc::intptr_t, item))
+            },
+            FarMessage::DnEnterIdle => send(ffi::FARMESSAGE::DN_ENTERIDLE as libc::intptr_t, 0, null),
+            FarMessage::DnGotFocus { id } => send(ffi::FARMESSAGE::DN_GOTFOCUS as libc::intptr_t, id as libc::intptr_t, null),
+            FarMessage::DnHelp { id, param, .. } => send(ffi::FARMESSAGE::DN_HELP as libc::intptr_t, id as libc::intptr_t, param.as_raw()),
+            FarMessage::DnHotKey { id, event } => send(ffi::FARMESSAGE::DN_HOTKEY as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut event.to_record())),
+            FarMessage::DnInitDialog { focus } => send(ffi::FARMESSAGE::DN_INITDIALOG as libc::intptr_t, focus as libc::intptr_t, null),
+            FarMessage::DnKillFocus { id } => send(ffi::FARMESSAGE::DN_KILLFOCUS as libc::intptr_t, id as libc::intptr_t, null),
+            FarMessage::DnListChange { id, pos } => send(ffi::FARMESSAGE::DN_LISTCHANGE as libc::intptr_t, id as libc::intptr_t, value(pos as libc::intptr_t)),
+            FarMessage::DnDragged { stage, cancelled } => send(ffi::FARMESSAGE::DN_DRAGGED as libc::intptr_t, stage, value(cancelled as libc::intptr_t)),
+            FarMessage::DnResizeConsole { mut size } => send(ffi::FARMESSAGE::DN_RESIZECONSOLE as libc::intptr_t, 0, encode_ptr(&mut size)),
+            FarMessage::DnDrawDialogDone => send(ffi::FARMESSAGE::DN_DRAWDIALOGDONE as libc::intptr_t, 0, null),
+            FarMessage::DnListHotKey { id, pos } => send(ffi::FARMESSAGE::DN_LISTHOTKEY as libc::intptr_t, id as libc::intptr_t, value(pos as libc::intptr_t)),
+            FarMessage::DnInput { event } => send(ffi::FARMESSAGE::DN_INPUT as libc::intptr_t, 0, encode_ptr(&mut event.to_record())),
+            FarMessage::DnControlInput { id, event } => send(ffi::FARMESSAGE::DN_CONTROLINPUT as libc::intptr_t, id as libc::intptr_t, encode_ptr(&mut event.to_record())),
+            FarMessage::DnClose { id } => send(ffi::FARMESSAGE::DN_CLOSE as libc::intptr_t, encode_exit_code(id), null),
+            FarMessage::DnGetValue { id, value } => send(ffi::FARMESSAGE::DN_GETVALUE as libc::intptr_t, id as libc::intptr_t, encode_ptr(value)),
+            FarMessage::DnDropdownOpened { id, opened } => send(ffi::FARMESSAGE::DN_DROPDOWNOPENED as libc::intptr_t, id as libc::intptr_t, value(opened as libc::intptr_t)),
+            FarMessage::DnDrawDlgItemDone { id, item, param } => {
+                encode_item(item, param, |item| send(ffi::FARMESSAGE::DN_DRAWDLGITEMDONE as libc::intptr_t, id as libc::intptr_t, item))
+            },
+            FarMessage::DmUser { param1, param2 } => send(ffi::FARMESSAGE::DM_USER as libc::intptr_t, param1, param2),
+            FarMessage::Raw { msg, param1, param2 } => send(msg, param1, param2),
         }
     }
 }
 
 pub trait FarDialog {
-    fn dlg_proc(&mut self, h_dlg: crate::HANDLE, msg: FarMessage) -> isize;
+    fn dlg_proc(&mut self, h_dlg: DialogHandle, msg: FarMessage) -> isize;
+}
+
+pub type ItemId = usize;
+
+#[derive(Clone, Copy)]
+pub struct ItemRect {
+    pub left: isize,
+    pub top: isize,
+    pub right: isize,
+    pub bottom: isize,
+}
+
+#[derive(Clone, Copy)]
+pub struct DialogHandle {
+    handle: ffi::HANDLE
+}
+
+impl From<crate::HANDLE> for DialogHandle {
+    fn from(handle: crate::HANDLE) -> Self {
+        DialogHandle {
+            handle
+        }
+    }
+}
+
+impl DialogHandle {
+
+    pub fn as_raw(&self) -> crate::HANDLE {
+        self.handle
+    }
+
+    pub fn send_message(&self, msg: FarMessage) -> isize {
+        msg.with_raw(|ffi_msg, param1, param2| far_api(|far_api: &mut ffi::PluginStartupInfo| {
+            far_api.send_dlg_message(self.handle, ffi_msg, param1, param2)
+        }))
+    }
+
+    pub fn get_text(&self, id: ItemId) -> crate::Result<WideString> {
+        let length = self.send(FARMESSAGE::DM_GETTEXT, id as libc::intptr_t, ptr::null_mut());
+        if length < 0 {
+            return Err(format_err!(""));
+        }
+
+        let mut buf: Vec<ffi::wchar_t> = vec![0; length as usize + 1];
+        let mut item_data = ffi::FarDialogItemData {
+            struct_size: mem::size_of::<ffi::FarDialogItemData>(),
+            ptr_length: length as libc::size_t,
+            ptr_data: buf.as_mut_ptr(),
+        };
+        let length = self.send(FARMESSAGE::DM_GETTEXT, id as libc::intptr_t,
+                               &mut item_data as *mut _ as *mut libc::c_void);
+        buf.truncate((length.max(0) as usize).min(item_data.ptr_length));
+        Ok(WideString::from(buf.as_slice()))
+    }
+
+    // Returns the length of the text set to the item.
+    pub fn set_text(&self, id: ItemId, text: &WideString) -> usize {
+        let mut item_data = ffi::FarDialogItemData {
+            struct_size: mem::size_of::<ffi::FarDialogItemData>(),
+            ptr_length: text.len(),
+            ptr_data: text.as_ptr() as *mut ffi::wchar_t,
+        };
+        self.send(FARMESSAGE::DM_SETTEXT, id as libc::intptr_t,
+                  &mut item_data as *mut _ as *mut libc::c_void) as usize
+    }
+
+    // Returns the previous state of the item.
+    pub fn enable(&self, id: ItemId, enable: bool) -> bool {
+        self.send(FARMESSAGE::DM_ENABLE, id as libc::intptr_t, enable as libc::intptr_t as *mut libc::c_void) != 0
+    }
+
+    pub fn is_enabled(&self, id: ItemId) -> bool {
+        self.send(FARMESSAGE::DM_ENABLE, id as libc::intptr_t, -1 as libc::intptr_t as *mut libc::c_void) != 0
+    }
+
+    // Returns the previous visibility of the item.
+    pub fn show_item(&self, id: ItemId, show: bool) -> bool {
+        self.send(FARMESSAGE::DM_SHOWITEM, id as libc::intptr_t, show as libc::intptr_t as *mut libc::c_void) != 0
+    }
+
+    pub fn set_focus(&self, id: ItemId) -> crate::Result<()> {
+        match self.send(FARMESSAGE::DM_SETFOCUS, id as libc::intptr_t, ptr::null_mut()) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        }
+    }
+
+    pub fn get_focus(&self) -> ItemId {
+        self.send(FARMESSAGE::DM_GETFOCUS, 0, ptr::null_mut()) as ItemId
+    }
+
+    // Returns the checked radio button of the group.
+    pub fn get_radio_selection(&self, group: Range<ItemId>) -> Option<ItemId> {
+        group.into_iter().find(|id| self.send(FARMESSAGE::DM_GETCHECK, *id as libc::intptr_t, ptr::null_mut()) != 0)
+    }
+
+    pub fn get_list_position(&self, id: ItemId) -> Option<usize> {
+        match self.send(FARMESSAGE::DM_LISTGETCURPOS, id as libc::intptr_t, ptr::null_mut()) {
+            pos if pos >= 0 => Some(pos as usize),
+            _ => None
+        }
+    }
+
+    // None if Far returned a state unknown to the binding.
+    pub fn get_check(&self, id: ItemId) -> Option<CheckBoxSelection> {
+        decode_check(self.send(FARMESSAGE::DM_GETCHECK, id as libc::intptr_t, ptr::null_mut()))
+    }
+
+    // Returns the previous state of the item.
+    pub fn set_check(&self, id: ItemId, state: CheckBoxSelection) -> Option<CheckBoxSelection> {
+        decode_check(self.send(FARMESSAGE::DM_SETCHECK, id as libc::intptr_t,
+                               state as libc::intptr_t as *mut libc::c_void))
+    }
+
+    // None closes the dialog as if Esc was pressed. Returns false if DN_CLOSE handler denied closing.
+    pub fn close(&self, exit_code: Option<ItemId>) -> bool {
+        self.send(FARMESSAGE::DM_CLOSE, encode_exit_code(exit_code), ptr::null_mut()) != 0
+    }
+
+    pub fn redraw(&self) {
+        self.send(FARMESSAGE::DM_REDRAW, 0, ptr::null_mut());
+    }
+
+    pub fn get_item_position(&self, id: ItemId) -> crate::Result<ItemRect> {
+        let mut rect = SMALL_RECT { Left: 0, Top: 0, Right: 0, Bottom: 0 };
+        match self.send(FARMESSAGE::DM_GETITEMPOSITION, id as libc::intptr_t,
+                        &mut rect as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(ItemRect {
+                left: rect.Left as isize,
+                top: rect.Top as isize,
+                right: rect.Right as isize,
+                bottom: rect.Bottom as isize,
+            })
+        }
+    }
+
+    // Moves the dialog to the absolute position or by the offset and returns its new top left corner.
+    pub fn move_dialog(&self, x: isize, y: isize, absolute: bool) -> (isize, isize) {
+        let mut coord = COORD { X: x as i16, Y: y as i16 };
+        self.send(FARMESSAGE::DM_MOVEDIALOG, absolute as libc::intptr_t,
+                  &mut coord as *mut _ as *mut libc::c_void);
+        (coord.X as isize, coord.Y as isize)
+    }
+
+    pub fn list(&self, id: ItemId) -> list::DialogList {
+        list::DialogList::new(*self, id)
+    }
+
+    fn send(&self, msg: ffi::FARMESSAGE, param1: libc::intptr_t, param2: *mut libc::c_void) -> libc::intptr_t {
+        far_api(|far_api: &mut ffi::PluginStartupInfo| {
+            far_api.send_dlg_message(self.handle, msg as libc::intptr_t, param1, param2)
+        })
+    }
 }
 
 pub struct Dialog<F: FarDialog> {
     handle: ffi::HANDLE,
     #[allow(dead_code)]
-    internal: Box<F>
+    internal: Box<F>,
+    #[allow(dead_code)]
+    items: Vec<FarDialogItem>,
+    #[allow(dead_code)]
+    lists: Vec<RawList>,
 }
 
 impl<F: FarDialog> Dialog<F> {
@@ -616,13 +1435,20 @@ impl<F: FarDialog> Dialog<F> {
                 help_topic: Option<WideString>, dialog_items: Vec<FarDialogItem>,
                 flags: ffi::FARDIALOGFLAGS, dialog: F) -> crate::Result<Self> {
 
+        if !dialog_items.iter().all(FarDialogItem::is_valid) {
+            return Err(format_err!(""));
+        }
+
         let mut internal = Box::new(dialog);
 
         let help_topic = match help_topic {
             Some(text) => text.as_ptr(),
             None => ptr::null(),
         };
-        let dialog_items_ffi: Vec<ffi::FarDialogItem> = (&dialog_items).iter().map(FarDialogItem::as_raw).collect();
+        let mut lists: Vec<RawList> = Vec::new();
+        let dialog_items_ffi: Vec<ffi::FarDialogItem> = (&dialog_items).iter()
+            .map(|item| item.as_raw(&mut lists))
+            .collect();
         let handle: ffi::HANDLE = far_api(|far_api: &mut ffi::PluginStartupInfo| {
             far_api.dialog_init(&plugin_id, &id, x1, y1, x2, y2, help_topic, dialog_items_ffi.as_ptr(), dialog_items_ffi.len(), 0, flags, callback::<F>, &mut *internal as *mut F as *mut libc::c_void)
         });
@@ -637,10 +1463,10 @@ impl<F: FarDialog> Dialog<F> {
             }) as *mut F;
 
             let dlg = unsafe { &mut *dlg_ptr };
-            let far_msg = FarMessage::from( unsafe { mem::transmute(msg as i32) }, param1, param2);
+            let far_msg = unsafe { FarMessage::from(msg, param1, param2) };
             let result = match far_msg {
                 Some(msg) => {
-                    dlg.dlg_proc(h_dlg, msg)
+                    dlg.dlg_proc(DialogHandle::from(h_dlg), msg)
                 },
                 None => far_api(|far_api: &mut ffi::PluginStartupInfo| {
                     far_api.def_dlg_proc(h_dlg, msg as libc::intptr_t, param1, param2)
@@ -651,14 +1477,42 @@ impl<F: FarDialog> Dialog<F> {
 
         Ok(Dialog {
             handle,
-            internal
+            internal,
+            items: dialog_items,
+            lists,
         })
     }
 
-    pub fn run(&self) {
-        far_api(|far_api: &mut ffi::PluginStartupInfo| {
-            far_api.dialog_run(self.handle);
-        })
+    pub fn handle(&self) -> DialogHandle {
+        DialogHandle::from(self.handle)
+    }
+
+    // Returns the item which closed the dialog or None if it was cancelled.
+    pub fn run(&self) -> Option<ItemId> {
+        let exit_code = far_api(|far_api: &mut ffi::PluginStartupInfo| {
+            far_api.dialog_run(self.handle)
+        });
+        match exit_code {
+            exit_code if exit_code >= 0 => Some(exit_code as ItemId),
+            _ => None
+        }
+    }
+
+    // Item state can be read after run() returned, till the dialog is dropped.
+    pub fn get_text(&self, id: ItemId) -> crate::Result<WideString> {
+        self.handle().get_text(id)
+    }
+
+    pub fn get_check(&self, id: ItemId) -> Option<CheckBoxSelection> {
+        self.handle().get_check(id)
+    }
+
+    pub fn get_radio_selection(&self, group: Range<ItemId>) -> Option<ItemId> {
+        self.handle().get_radio_selection(group)
+    }
+
+    pub fn get_list_position(&self, id: ItemId) -> Option<usize> {
+        self.handle().get_list_position(id)
     }
 }
 
@@ -670,11 +1524,10 @@ impl<F: FarDialog> Drop for Dialog<F> {
     }
 }
 
-pub fn def_dlg_proc(h_dlg: crate::HANDLE, msg: FarMessage) -> isize {
-    let (ffi_msg, param1, param2) = msg.into();
-    return far_api(|far_api: &mut ffi::PluginStartupInfo| {
-        far_api.def_dlg_proc(h_dlg, ffi_msg as libc::intptr_t, param1, param2)
-    })
+pub fn def_dlg_proc(h_dlg: DialogHandle, msg: FarMessage) -> isize {
+    msg.with_raw(|ffi_msg, param1, param2| far_api(|far_api: &mut ffi::PluginStartupInfo| {
+        far_api.def_dlg_proc(h_dlg.as_raw(), ffi_msg, param1, param2)
+    }))
 }
 
 #[allow(unused_variables)]
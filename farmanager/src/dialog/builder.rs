@@ -0,0 +1,554 @@
+use std::ops::Range;
+
+use super::*;
+
+// Columns between the dialog edge and the double box, and between a box and its items.
+const DIALOG_MARGIN: usize = 3;
+const BOX_PADDING: usize = 2;
+// Columns between the items of a row.
+const ITEM_GAP: usize = 1;
+
+enum Control {
+    DoubleBox { title: Option<WideString> },
+    SingleBox { title: Option<WideString> },
+    Label { text: WideString },
+    Separator,
+    Edit { width: usize, text: Option<WideString> },
+    CheckBox { selected: u8, text: WideString },
+    RadioButton { selected: u8, text: WideString },
+    Button { text: WideString },
+    ComboBox { width: usize, items: Vec<ListItem>, text: Option<WideString> },
+    ListBox { width: usize, height: usize, items: Vec<ListItem> },
+}
+
+struct Entry {
+    control: Control,
+    flags: FARDIALOGITEMFLAGS,
+}
+
+impl Entry {
+    fn size(&self) -> (usize, usize) {
+        match &self.control {
+            Control::DoubleBox { .. } | Control::SingleBox { .. } => (0, 0),
+            Control::Label { text } => (text_width(text), 1),
+            Control::Separator => (0, 1),
+            Control::Edit { width, .. } => (*width, 1),
+            Control::CheckBox { text, .. } | Control::RadioButton { text, .. } | Control::Button { text } => {
+                (text_width(text) + 4, 1)
+            },
+            Control::ComboBox { width, .. } => (*width, 1),
+            Control::ListBox { width, height, .. } => (*width, *height),
+        }
+    }
+
+    // Stretched items take the whole width given to them.
+    fn is_stretched(&self) -> bool {
+        match &self.control {
+            Control::Edit { .. } | Control::ComboBox { .. } | Control::ListBox { .. } => true,
+            _ => false
+        }
+    }
+
+    fn to_item(&self, rect: &ItemRect) -> FarDialogItem {
+        let flags = self.flags;
+        match &self.control {
+            Control::DoubleBox { title } => FarDialogItem::DoubleBox {
+                x1: rect.left, y1: rect.top, x2: rect.right, y2: rect.bottom, flags, title: title.clone()
+            },
+            Control::SingleBox { title } => FarDialogItem::SingleBox {
+                x1: rect.left, y1: rect.top, x2: rect.right, y2: rect.bottom, flags, title: title.clone()
+            },
+            Control::Label { text } => FarDialogItem::Text {
+                x1: rect.left, y: rect.top, x2: rect.right, mask: None, flags, text: Some(text.clone())
+            },
+            Control::Separator => FarDialogItem::Text {
+                x1: rect.left, y: rect.top, x2: rect.right, mask: None,
+                flags: flags | FARDIALOGITEMFLAGS::DIF_SEPARATOR, text: None
+            },
+            Control::Edit { text, .. } => FarDialogItem::Edit {
+                x1: rect.left, y: rect.top, x2: rect.right, history: None, flags, text: text.clone()
+            },
+            Control::CheckBox { selected, text } => FarDialogItem::CheckBox {
+                x: rect.left, y: rect.top, selected: DialogItemSelection { value: *selected }, flags, text: text.clone()
+            },
+            Control::RadioButton { selected, text } => FarDialogItem::RadioButton {
+                x: rect.left, y: rect.top, selected: DialogItemSelection { value: *selected }, flags, text: text.clone()
+            },
+            Control::Button { text } => FarDialogItem::Button {
+                x: rect.left, y: rect.top, selected: ButtonSelection::Deselected.into(), flags, text: text.clone()
+            },
+            Control::ComboBox { items, text, .. } => FarDialogItem::ComboBox {
+                x1: rect.left, y: rect.top, x2: rect.right, items: items.clone(), flags, text: text.clone()
+            },
+            Control::ListBox { items, .. } => FarDialogItem::ListBox {
+                x1: rect.left, y1: rect.top, x2: rect.right, y2: rect.bottom, items: items.clone(), flags, title: None
+            },
+        }
+    }
+}
+
+// Columns taken by the text on the screen. Hotkey markers are not shown and "&&" is shown as '&'.
+fn text_width(text: &WideString) -> usize {
+    let mut width = 0;
+    let mut marker = false;
+    for c in std::char::decode_utf16(text.as_slice().iter().cloned()) {
+        let c = c.unwrap_or(std::char::REPLACEMENT_CHARACTER);
+        if c == '&' && !marker {
+            marker = true;
+            continue;
+        }
+        marker = false;
+        width += char_width(c);
+    }
+    width
+}
+
+// East Asian wide and fullwidth characters take two columns.
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115F | 0x2E80..=0x303E | 0x3041..=0x33FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF |
+        0xA000..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 |
+        0xFFE0..=0xFFE6 | 0x1F300..=0x1F64F | 0x1F900..=0x1F9FF | 0x20000..=0x2FFFD | 0x30000..=0x3FFFD => 2,
+        _ => 1
+    }
+}
+
+enum Node {
+    Item(ItemId),
+    Row(Vec<Node>),
+    Column(Vec<Node>),
+    // the children are placed as a column inside the box
+    Group(ItemId, Vec<Node>),
+    Buttons(Range<ItemId>),
+}
+
+impl Node {
+    fn children_mut(&mut self) -> Option<&mut Vec<Node>> {
+        match self {
+            Node::Row(children) | Node::Column(children) | Node::Group(_, children) => Some(children),
+            _ => None
+        }
+    }
+}
+
+// Places the items in rows and columns instead of the coordinates given by hand. Item ids are given
+// in the creation order and are the same as the item indexes of the built dialog, the double box
+// around the dialog is the item 0. Items are added to the column of the dialog or to the innermost
+// row, column or group began and not ended yet.
+pub struct DialogBuilder {
+    entries: Vec<Entry>,
+    stack: Vec<Node>,
+    max_width: Option<usize>,
+}
+
+impl DialogBuilder {
+
+    pub fn new(title: Option<WideString>) -> Self {
+        DialogBuilder {
+            entries: vec![Entry {
+                control: Control::DoubleBox { title },
+                flags: FARDIALOGITEMFLAGS::DIF_NONE,
+            }],
+            stack: vec![Node::Column(Vec::new())],
+            max_width: None,
+        }
+    }
+
+    // Lets DialogLayout::relayout widen the dialog up to the given width of the items when the console
+    // grows. Without it the dialog keeps the width required by the items.
+    pub fn max_width(&mut self, width: usize) -> &mut Self {
+        self.max_width = Some(width);
+        self
+    }
+
+    pub fn begin_row(&mut self) {
+        self.stack.push(Node::Row(Vec::new()));
+    }
+
+    pub fn begin_column(&mut self) {
+        self.stack.push(Node::Column(Vec::new()));
+    }
+
+    // Returns the id of the box drawn around the group.
+    pub fn begin_group(&mut self, title: Option<WideString>) -> ItemId {
+        let id = self.add_entry(Control::SingleBox { title }, FARDIALOGITEMFLAGS::DIF_NONE);
+        self.stack.push(Node::Group(id, Vec::new()));
+        id
+    }
+
+    // Ends the innermost row, column or group.
+    pub fn end(&mut self) {
+        if self.stack.len() > 1 {
+            let node = self.stack.pop().unwrap();
+            self.add_node(node);
+        }
+    }
+
+    pub fn label(&mut self, text: WideString) -> ItemId {
+        self.add_item(Control::Label { text }, FARDIALOGITEMFLAGS::DIF_NONE)
+    }
+
+    // Draws a line across the dialog or across the group it is added to.
+    pub fn separator(&mut self) -> ItemId {
+        self.add_item(Control::Separator, FARDIALOGITEMFLAGS::DIF_NONE)
+    }
+
+    pub fn edit(&mut self, width: usize, text: Option<WideString>) -> ItemId {
+        self.add_item(Control::Edit { width, text }, FARDIALOGITEMFLAGS::DIF_NONE)
+    }
+
+    pub fn check_box(&mut self, text: WideString, selected: CheckBoxSelection) -> ItemId {
+        let flags = match selected {
+            CheckBoxSelection::Undefined => FARDIALOGITEMFLAGS::DIF_3STATE,
+            _ => FARDIALOGITEMFLAGS::DIF_NONE
+        };
+        self.add_item(Control::CheckBox { selected: selected as u8, text }, flags)
+    }
+
+    // Adds the radio buttons one by one to the current container, the range can be passed to
+    // get_radio_selection.
+    pub fn radio_group(&mut self, texts: &[WideString], selected: usize) -> Range<ItemId> {
+        let start = self.entries.len();
+        for (index, text) in texts.iter().enumerate() {
+            let flags = match index {
+                0 => FARDIALOGITEMFLAGS::DIF_GROUP,
+                _ => FARDIALOGITEMFLAGS::DIF_NONE
+            };
+            let selected = match index == selected {
+                true => RadioButtonSelection::Active,
+                false => RadioButtonSelection::Previous
+            };
+            self.add_item(Control::RadioButton { selected: selected as u8, text: text.clone() }, flags);
+        }
+        start..self.entries.len()
+    }
+
+    pub fn combo_box(&mut self, width: usize, items: Vec<ListItem>, text: Option<WideString>) -> ItemId {
+        self.add_item(Control::ComboBox { width, items, text }, FARDIALOGITEMFLAGS::DIF_NONE)
+    }
+
+    pub fn list_box(&mut self, width: usize, height: usize, items: Vec<ListItem>) -> ItemId {
+        self.add_item(Control::ListBox { width, height, items }, FARDIALOGITEMFLAGS::DIF_NONE)
+    }
+
+    // Adds the buttons centered in one line, the first one is the default button.
+    pub fn button_row(&mut self, texts: &[WideString]) -> Range<ItemId> {
+        let start = self.entries.len();
+        for (index, text) in texts.iter().enumerate() {
+            let flags = match index {
+                0 => FARDIALOGITEMFLAGS::DIF_DEFAULTBUTTON,
+                _ => FARDIALOGITEMFLAGS::DIF_NONE
+            };
+            self.add_entry(Control::Button { text: text.clone() }, flags);
+        }
+        let buttons = start..self.entries.len();
+        self.add_node(Node::Buttons(buttons.clone()));
+        buttons
+    }
+
+    // Adds the flags to the ones the item got from the builder.
+    pub fn add_flags(&mut self, id: ItemId, flags: FARDIALOGITEMFLAGS) {
+        self.entries[id].flags |= flags;
+    }
+
+    // Ends the rows, columns and groups left open.
+    pub fn build(mut self) -> DialogLayout {
+        while self.stack.len() > 1 {
+            self.end();
+        }
+        let root = self.stack.pop().unwrap();
+        DialogLayout::new(self.entries, root, self.max_width)
+    }
+
+    fn add_item(&mut self, control: Control, flags: FARDIALOGITEMFLAGS) -> ItemId {
+        let id = self.add_entry(control, flags);
+        self.add_node(Node::Item(id));
+        id
+    }
+
+    fn add_entry(&mut self, control: Control, flags: FARDIALOGITEMFLAGS) -> ItemId {
+        self.entries.push(Entry {
+            control,
+            flags,
+        });
+        self.entries.len() - 1
+    }
+
+    fn add_node(&mut self, node: Node) {
+        if let Some(children) = self.stack.last_mut().and_then(Node::children_mut) {
+            children.push(node);
+        }
+    }
+}
+
+pub struct DialogLayout {
+    entries: Vec<Entry>,
+    root: Node,
+    max_width: Option<usize>,
+    min_width: usize,
+    width: usize,
+    height: usize,
+    console_height: Option<usize>,
+}
+
+impl DialogLayout {
+
+    fn new(entries: Vec<Entry>, root: Node, max_width: Option<usize>) -> Self {
+        let mut layout = DialogLayout {
+            entries,
+            root,
+            max_width,
+            min_width: 0,
+            width: 0,
+            height: 0,
+            console_height: None,
+        };
+        let (width, height) = layout.measure(&layout.root);
+        let title_width = match &layout.entries[0].control {
+            Control::DoubleBox { title: Some(title) } => text_width(title) + 2,
+            _ => 0
+        };
+        layout.min_width = width.max(title_width);
+        layout.width = layout.min_width;
+        layout.height = height;
+        layout
+    }
+
+    // Width and height to pass to Dialog::init with the dialog centered.
+    pub fn size(&self) -> (isize, isize) {
+        let padding = 2 * (DIALOG_MARGIN + BOX_PADDING);
+        let height = match self.console_height {
+            Some(console_height) => (self.height + 4).min(console_height),
+            None => self.height + 4
+        };
+        ((self.width + padding) as isize, height as isize)
+    }
+
+    pub fn items(&self) -> Vec<FarDialogItem> {
+        self.rects().iter()
+            .zip(self.entries.iter())
+            .map(|(rect, entry)| entry.to_item(rect))
+            .collect()
+    }
+
+    // Fits the dialog to the console, should be called from dlg_proc on DN_RESIZECONSOLE. On a console
+    // smaller than the items the dialog is cut to the console, the items keep their size and the part
+    // of them outside of the dialog isn't visible.
+    pub fn relayout(&mut self, handle: DialogHandle, console: &COORD) {
+        self.fit(console);
+
+        let (width, height) = self.size();
+        let size = COORD { X: width as i16, Y: height as i16 };
+        handle.send_message(FarMessage::DmEnableRedraw { enable: Some(false) });
+        handle.send_message(FarMessage::DmResizeDialog { size });
+        for (id, rect) in self.rects().into_iter().enumerate() {
+            handle.send_message(FarMessage::DmSetItemPosition { id, rect });
+        }
+        handle.move_dialog(-1, -1, true);
+        handle.send_message(FarMessage::DmEnableRedraw { enable: Some(true) });
+    }
+
+    fn fit(&mut self, console: &COORD) {
+        let available = (console.X.max(0) as usize).saturating_sub(2 * (DIALOG_MARGIN + BOX_PADDING));
+        let width = match self.max_width {
+            Some(max_width) => available.min(max_width).max(self.min_width),
+            None => self.min_width
+        };
+        self.width = width.min(available);
+        self.console_height = Some(console.Y.max(0) as usize);
+    }
+
+    fn rects(&self) -> Vec<ItemRect> {
+        let mut rects = vec![ItemRect { left: 0, top: 0, right: 0, bottom: 0 }; self.entries.len()];
+        let (width, height) = self.size();
+        rects[0] = ItemRect {
+            left: DIALOG_MARGIN as isize,
+            top: 1,
+            right: width - 1 - DIALOG_MARGIN as isize,
+            bottom: height - 2,
+        };
+        // separators of the dialog column are stretched by Far
+        self.place(&self.root, (DIALOG_MARGIN + BOX_PADDING) as isize, 2, self.width, (0, 0), &mut rects);
+        rects
+    }
+
+    fn measure(&self, node: &Node) -> (usize, usize) {
+        match node {
+            Node::Item(id) => self.entries[*id].size(),
+            Node::Row(children) => row_size(children.iter().map(|child| self.measure(child))),
+            Node::Column(children) => column_size(children.iter().map(|child| self.measure(child))),
+            Node::Group(_, children) => {
+                let (width, height) = column_size(children.iter().map(|child| self.measure(child)));
+                (width + 2 * BOX_PADDING, height + 2)
+            },
+            Node::Buttons(ids) => row_size(ids.clone().map(|id| self.entries[id].size())),
+        }
+    }
+
+    fn is_stretched(&self, node: &Node) -> bool {
+        match node {
+            Node::Item(id) => self.entries[*id].is_stretched(),
+            Node::Row(children) | Node::Column(children) => children.iter().any(|child| self.is_stretched(child)),
+            Node::Group(..) => true,
+            Node::Buttons(..) => false,
+        }
+    }
+
+    // Places the node at x, y within the width. separator is the left and right column of the lines
+    // drawn by the separators of the node.
+    fn place(&self, node: &Node, x: isize, y: isize, width: usize, separator: (isize, isize),
+             rects: &mut [ItemRect]) {
+        match node {
+            Node::Item(id) => {
+                let entry = &self.entries[*id];
+                let (item_width, item_height) = entry.size();
+                rects[*id] = match entry.control {
+                    Control::Separator => ItemRect { left: separator.0, top: y, right: separator.1, bottom: y },
+                    _ => {
+                        let item_width = if entry.is_stretched() { width } else { item_width };
+                        ItemRect {
+                            left: x,
+                            top: y,
+                            right: x + item_width as isize - 1,
+                            bottom: y + item_height as isize - 1,
+                        }
+                    }
+                };
+            },
+            Node::Row(children) => {
+                let sizes: Vec<(usize, usize)> = children.iter().map(|child| self.measure(child)).collect();
+                let stretched = children.iter().filter(|child| self.is_stretched(child)).count();
+                let extra = width.saturating_sub(row_size(sizes.iter().cloned()).0);
+
+                let mut x = x;
+                let mut index = 0;
+                for (child, (child_width, _)) in children.iter().zip(sizes) {
+                    let mut child_width = child_width;
+                    if self.is_stretched(child) {
+                        child_width += extra / stretched + if index < extra % stretched { 1 } else { 0 };
+                        index += 1;
+                    }
+                    self.place(child, x, y, child_width, separator, rects);
+                    x += (child_width + ITEM_GAP) as isize;
+                }
+            },
+            Node::Column(children) => {
+                let mut y = y;
+                for child in children {
+                    self.place(child, x, y, width, separator, rects);
+                    y += self.measure(child).1 as isize;
+                }
+            },
+            Node::Group(id, children) => {
+                let (_, height) = self.measure(node);
+                let right = x + width as isize - 1;
+                rects[*id] = ItemRect { left: x, top: y, right, bottom: y + height as isize - 1 };
+
+                let mut child_y = y + 1;
+                for child in children {
+                    self.place(child, x + BOX_PADDING as isize, child_y, width.saturating_sub(2 * BOX_PADDING),
+                               (x, right), rects);
+                    child_y += self.measure(child).1 as isize;
+                }
+            },
+            Node::Buttons(ids) => {
+                let (buttons_width, _) = self.measure(node);
+                let mut x = x + (width.saturating_sub(buttons_width) / 2) as isize;
+                for id in ids.clone() {
+                    let (button_width, _) = self.entries[id].size();
+                    rects[id] = ItemRect { left: x, top: y, right: x + button_width as isize - 1, bottom: y };
+                    x += (button_width + ITEM_GAP) as isize;
+                }
+            },
+        }
+    }
+}
+
+fn row_size(sizes: impl Iterator<Item=(usize, usize)>) -> (usize, usize) {
+    let (width, height, count) = sizes.fold((0, 0, 0), |(width, height, count), (item_width, item_height)| {
+        (width + item_width, height.max(item_height), count + 1)
+    });
+    (width + ITEM_GAP * count.max(1) - ITEM_GAP, height)
+}
+
+fn column_size(sizes: impl Iterator<Item=(usize, usize)>) -> (usize, usize) {
+    sizes.fold((0, 0), |(width, height), (item_width, item_height)| (width.max(item_width), height + item_height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(rect: &ItemRect) -> (isize, isize, isize, isize) {
+        (rect.left, rect.top, rect.right, rect.bottom)
+    }
+
+    #[test]
+    fn text_width_counts_columns() {
+        assert_eq!(text_width(&WideString::from("&Ok")), 2);
+        assert_eq!(text_width(&WideString::from("A&&B")), 3);
+        assert_eq!(text_width(&WideString::from("\u{65e5}\u{672c}")), 4);
+        assert_eq!(text_width(&WideString::from("\u{1d11e}")), 1);
+    }
+
+    #[test]
+    fn row_stretches_items() {
+        let mut builder = DialogBuilder::new(None);
+        builder.begin_row();
+        builder.label(WideString::from("Name:"));
+        builder.edit(10, None);
+        builder.end();
+        builder.label(WideString::from("0123456789012345678901"));
+        let layout = builder.build();
+
+        let rects = layout.rects();
+        assert_eq!(layout.size(), (32, 6));
+        assert_eq!(bounds(&rects[0]), (3, 1, 28, 4));
+        assert_eq!(bounds(&rects[1]), (5, 2, 9, 2));
+        assert_eq!(bounds(&rects[2]), (11, 2, 26, 2));
+        assert_eq!(bounds(&rects[3]), (5, 3, 26, 3));
+    }
+
+    #[test]
+    fn group_boxes_its_items() {
+        let mut builder = DialogBuilder::new(None);
+        builder.begin_group(Some(WideString::from("G")));
+        builder.check_box(WideString::from("Opt"), CheckBoxSelection::Deselected);
+        builder.separator();
+        builder.end();
+        let layout = builder.build();
+
+        let rects = layout.rects();
+        assert_eq!(bounds(&rects[1]), (5, 2, 15, 5));
+        assert_eq!(bounds(&rects[2]), (7, 3, 13, 3));
+        assert_eq!(bounds(&rects[3]), (5, 4, 15, 4));
+    }
+
+    #[test]
+    fn button_row_is_centered() {
+        let mut builder = DialogBuilder::new(None);
+        builder.label(WideString::from("012345678901234567890"));
+        let buttons = builder.button_row(&[WideString::from("&Ok"), WideString::from("Cancel")]);
+        let layout = builder.build();
+
+        let rects = layout.rects();
+        assert_eq!(buttons, 2..4);
+        assert_eq!(bounds(&rects[2]), (7, 3, 12, 3));
+        assert_eq!(bounds(&rects[3]), (14, 3, 23, 3));
+    }
+
+    #[test]
+    fn fit_clamps_to_console() {
+        let mut builder = DialogBuilder::new(None);
+        builder.max_width(40);
+        builder.edit(10, None);
+        builder.edit(10, None);
+        let mut layout = builder.build();
+
+        layout.fit(&COORD { X: 80, Y: 25 });
+        assert_eq!(layout.size(), (50, 6));
+        layout.fit(&COORD { X: 16, Y: 4 });
+        assert_eq!(layout.size(), (16, 4));
+        assert_eq!(bounds(&layout.rects()[0]), (3, 1, 12, 2));
+    }
+}
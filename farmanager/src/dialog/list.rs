@@ -0,0 +1,193 @@
+use std::mem;
+use std::ptr;
+
+use failure::*;
+
+pub use crate::ffi::FARLISTFINDFLAGS as FARLISTFINDFLAGS;
+
+use super::*;
+
+// Value which can be stored as list item data: it must have no padding, no pointers and every bit
+// pattern must be a valid value, because Far keeps a byte copy of it.
+pub unsafe trait ListData: Copy {}
+
+macro_rules! list_data {
+    ($($t:ty),*) => { $(unsafe impl ListData for $t {})* }
+}
+
+list_data!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+pub struct DialogList {
+    handle: DialogHandle,
+    id: ItemId,
+}
+
+impl DialogList {
+
+    pub(super) fn new(handle: DialogHandle, id: ItemId) -> Self {
+        DialogList {
+            handle,
+            id
+        }
+    }
+
+    pub fn id(&self) -> ItemId {
+        self.id
+    }
+
+    pub fn len(&self) -> usize {
+        let mut info: ffi::FarListInfo = unsafe { mem::zeroed() };
+        info.struct_size = mem::size_of::<ffi::FarListInfo>();
+        match self.send(FARMESSAGE::DM_LISTINFO, &mut info as *mut _ as *mut libc::c_void) {
+            0 => 0,
+            _ => info.items_number
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn add(&self, items: &[ListItem]) -> crate::Result<()> {
+        let mut list = RawList::new(items);
+        match self.send(FARMESSAGE::DM_LISTADD, list.as_ptr() as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        }
+    }
+
+    // Returns the index of the inserted item.
+    pub fn insert(&self, index: usize, item: &ListItem) -> crate::Result<usize> {
+        let mut insert = ffi::FarListInsert {
+            struct_size: mem::size_of::<ffi::FarListInsert>(),
+            index: index as libc::intptr_t,
+            item: item.as_raw(),
+        };
+        match self.send(FARMESSAGE::DM_LISTINSERT, &mut insert as *mut _ as *mut libc::c_void) {
+            index if index >= 0 => Ok(index as usize),
+            _ => Err(format_err!(""))
+        }
+    }
+
+    // Returns the number of items left in the list.
+    pub fn delete(&self, index: usize, count: usize) -> usize {
+        let mut delete = ffi::FarListDelete {
+            struct_size: mem::size_of::<ffi::FarListDelete>(),
+            start_index: index as libc::intptr_t,
+            count: count as libc::intptr_t,
+        };
+        self.send(FARMESSAGE::DM_LISTDELETE, &mut delete as *mut _ as *mut libc::c_void) as usize
+    }
+
+    pub fn clear(&self) {
+        self.send(FARMESSAGE::DM_LISTDELETE, ptr::null_mut());
+    }
+
+    pub fn update(&self, index: usize, item: &ListItem) -> crate::Result<()> {
+        let mut update = ffi::FarListUpdate {
+            struct_size: mem::size_of::<ffi::FarListUpdate>(),
+            index: index as libc::intptr_t,
+            item: item.as_raw(),
+        };
+        match self.send(FARMESSAGE::DM_LISTUPDATE, &mut update as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        }
+    }
+
+    pub fn find(&self, start_index: usize, pattern: &WideString, flags: FARLISTFINDFLAGS) -> Option<usize> {
+        let mut find = ffi::FarListFind {
+            struct_size: mem::size_of::<ffi::FarListFind>(),
+            start_index: start_index as libc::intptr_t,
+            pattern: pattern.as_ptr(),
+            flags,
+        };
+        match self.send(FARMESSAGE::DM_LISTFINDSTRING, &mut find as *mut _ as *mut libc::c_void) {
+            index if index >= 0 => Some(index as usize),
+            _ => None
+        }
+    }
+
+    pub fn get_position(&self) -> Option<usize> {
+        self.handle.get_list_position(self.id)
+    }
+
+    // top is the index of the first visible item, None keeps it chosen by Far. Returns the new position.
+    pub fn set_position(&self, pos: usize, top: Option<usize>) -> usize {
+        let mut list_pos = ffi::FarListPos {
+            struct_size: mem::size_of::<ffi::FarListPos>(),
+            select_pos: pos as libc::intptr_t,
+            top_pos: top.map_or(-1, |top| top as libc::intptr_t),
+        };
+        self.send(FARMESSAGE::DM_LISTSETCURPOS, &mut list_pos as *mut _ as *mut libc::c_void) as usize
+    }
+
+    pub fn set_titles(&self, title: Option<&WideString>, bottom: Option<&WideString>) -> crate::Result<()> {
+        let mut titles = ffi::FarListTitles {
+            struct_size: mem::size_of::<ffi::FarListTitles>(),
+            title_size: title.map_or(0, |title| title.len() + 1),
+            title: title.map_or(ptr::null(), |title| title.as_ptr()),
+            bottom_size: bottom.map_or(0, |bottom| bottom.len() + 1),
+            bottom: bottom.map_or(ptr::null(), |bottom| bottom.as_ptr()),
+        };
+        match self.send(FARMESSAGE::DM_LISTSETTITLES, &mut titles as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        }
+    }
+
+    // Returns the title and the bottom line of the list.
+    pub fn get_titles(&self) -> crate::Result<(WideString, WideString)> {
+        let mut titles = ffi::FarListTitles {
+            struct_size: mem::size_of::<ffi::FarListTitles>(),
+            title_size: 0,
+            title: ptr::null(),
+            bottom_size: 0,
+            bottom: ptr::null(),
+        };
+        if self.send(FARMESSAGE::DM_LISTGETTITLES, &mut titles as *mut _ as *mut libc::c_void) == 0 {
+            return Err(format_err!(""));
+        }
+
+        let mut title: Vec<ffi::wchar_t> = vec![0; titles.title_size + 1];
+        let mut bottom: Vec<ffi::wchar_t> = vec![0; titles.bottom_size + 1];
+        titles.title_size = title.len();
+        titles.title = title.as_mut_ptr();
+        titles.bottom_size = bottom.len();
+        titles.bottom = bottom.as_mut_ptr();
+        match self.send(FARMESSAGE::DM_LISTGETTITLES, &mut titles as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok((WideString::from(title.as_slice()), WideString::from(bottom.as_slice())))
+        }
+    }
+
+    // Far keeps a copy of the data, so only plain values are accepted.
+    pub fn set_data<T: ListData>(&self, index: usize, data: &T) -> crate::Result<()> {
+        let mut item_data = ffi::FarListItemData {
+            struct_size: mem::size_of::<ffi::FarListItemData>(),
+            index: index as libc::intptr_t,
+            data_size: mem::size_of::<T>(),
+            data: data as *const T as *mut libc::c_void,
+        };
+        match self.send(FARMESSAGE::DM_LISTSETDATA, &mut item_data as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        }
+    }
+
+    // Returns None if the item has no data or it has a different size.
+    pub fn get_data<T: ListData>(&self, index: usize) -> Option<T> {
+        let size = self.send(FARMESSAGE::DM_LISTGETDATASIZE, index as libc::intptr_t as *mut libc::c_void);
+        if size as usize != mem::size_of::<T>() {
+            return None;
+        }
+        match self.send(FARMESSAGE::DM_LISTGETDATA, index as libc::intptr_t as *mut libc::c_void) {
+            0 => None,
+            data => Some(unsafe { ptr::read_unaligned(data as *const T) })
+        }
+    }
+
+    fn send(&self, msg: ffi::FARMESSAGE, param2: *mut libc::c_void) -> libc::intptr_t {
+        self.handle.send(msg, self.id as libc::intptr_t, param2)
+    }
+}
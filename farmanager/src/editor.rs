@@ -1,15 +1,1452 @@
+#[cfg(feature = "editor")]
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+#[allow(unused_imports)] // till IntelliJ Rust stop to highlight is as an error
+use std::panic;
+use std::ptr;
+use std::result;
+use std::slice;
+
+use bitflags::bitflags;
+use failure::*;
 use log::*;
 
+use crate::basic;
+use crate::basic::InputEvent;
 use crate::common::string::WideString;
 use crate::far_api;
+#[allow(unused_imports)] // TODO remove after IntelliJ Rust will stop to highlight is as an error
+use crate::FarPlugin;
 use crate::ffi;
+pub use crate::ffi::EDITOR_BLOCK_TYPES as EDITOR_BLOCK_TYPES;
+pub use crate::ffi::EDITOR_FLAGS as EDITOR_FLAGS;
+pub use crate::ffi::EDITORCOLORFLAGS as EDITORCOLORFLAGS;
+pub use crate::ffi::EXPAND_TABS as EXPAND_TABS;
+pub use crate::ffi::FarColor as FarColor;
+#[allow(unused_imports)] // TODO remove after IntelliJ Rust will stop to highlight is as an error
+use crate::plugin;
+use crate::Result;
+
+mod ctx;
+pub mod completion;
+pub mod search;
+pub mod transform;
+
+thread_local! {
+    static CONTEXT: RefCell<Option<ctx::Context>> = RefCell::new(None);
+}
+
+pub trait ExportFunctions {
+
+    #[allow(unused_variables)]
+    fn process_editor_event(&mut self, info: ProcessEditorEventInfo) {}
+    #[allow(unused_variables)]
+    fn process_editor_input(&mut self, info: ProcessEditorInputInfo) -> bool {
+        false
+    }
+}
+
+pub struct ProcessEditorInputInfo {
+    pub event: InputEvent,
+}
+
+pub enum EditorChange {
+    LineAdded(usize),
+    LineDeleted(usize),
+    LineChanged(usize),
+}
+
+impl From<&ffi::EditorChange> for EditorChange {
+    fn from(change: &ffi::EditorChange) -> Self {
+        let line = change.string_number as usize;
+        match change.change_type {
+            ffi::EDITOR_CHANGETYPE::ECTYPE_ADDED => EditorChange::LineAdded(line),
+            ffi::EDITOR_CHANGETYPE::ECTYPE_DELETED => EditorChange::LineDeleted(line),
+            ffi::EDITOR_CHANGETYPE::ECTYPE_CHANGED => EditorChange::LineChanged(line),
+        }
+    }
+}
+
+pub enum EditorEvent {
+    Read,
+    Save {
+        file: WideString,
+        codepage: libc::uintptr_t,
+    },
+    Redraw,
+    Close,
+    GotFocus,
+    KillFocus,
+    Change(EditorChange),
+}
+
+pub struct ProcessEditorEventInfo {
+    pub event: EditorEvent,
+    pub editor_id: libc::intptr_t,
+}
+
+impl ProcessEditorEventInfo {
+    #[allow(dead_code)]
+    fn from(info: &ffi::ProcessEditorEventInfo) -> Option<Self> {
+        let event = match info.event {
+            e if e == ffi::EDITOR_EVENTS::EE_READ as libc::intptr_t => EditorEvent::Read,
+            e if e == ffi::EDITOR_EVENTS::EE_SAVE as libc::intptr_t => {
+                let save_file = unsafe { &*(info.param as *const ffi::EditorSaveFile) };
+                EditorEvent::Save {
+                    file: unsafe { WideString::from_ptr_str(save_file.file_name) },
+                    codepage: save_file.code_page,
+                }
+            },
+            e if e == ffi::EDITOR_EVENTS::EE_REDRAW as libc::intptr_t => EditorEvent::Redraw,
+            e if e == ffi::EDITOR_EVENTS::EE_CLOSE as libc::intptr_t => EditorEvent::Close,
+            e if e == ffi::EDITOR_EVENTS::EE_GOTFOCUS as libc::intptr_t => EditorEvent::GotFocus,
+            e if e == ffi::EDITOR_EVENTS::EE_KILLFOCUS as libc::intptr_t => EditorEvent::KillFocus,
+            e if e == ffi::EDITOR_EVENTS::EE_CHANGE as libc::intptr_t => {
+                let change = unsafe { &*(info.param as *const ffi::EditorChange) };
+                EditorEvent::Change(EditorChange::from(change))
+            },
+            _ => return None
+        };
+        Some(ProcessEditorEventInfo {
+            event,
+            editor_id: info.editor_id,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Editor {
+    id: libc::intptr_t
+}
+
+impl From<libc::intptr_t> for Editor {
+    fn from(id: libc::intptr_t) -> Self {
+        Editor {
+            id
+        }
+    }
+}
+
+bitflags! {
+    #[allow(non_camel_case_types)]
+    pub struct EDITOR_OPTIONS: libc::uintptr_t {
+        const EOPT_EXPANDALLTABS      = ffi::EDITOR_OPTIONS::EOPT_EXPANDALLTABS as libc::uintptr_t;
+        const EOPT_PERSISTENTBLOCKS   = ffi::EDITOR_OPTIONS::EOPT_PERSISTENTBLOCKS as libc::uintptr_t;
+        const EOPT_DELREMOVESBLOCKS   = ffi::EDITOR_OPTIONS::EOPT_DELREMOVESBLOCKS as libc::uintptr_t;
+        const EOPT_AUTOINDENT         = ffi::EDITOR_OPTIONS::EOPT_AUTOINDENT as libc::uintptr_t;
+        const EOPT_SAVEFILEPOSITION   = ffi::EDITOR_OPTIONS::EOPT_SAVEFILEPOSITION as libc::uintptr_t;
+        const EOPT_AUTODETECTCODEPAGE = ffi::EDITOR_OPTIONS::EOPT_AUTODETECTCODEPAGE as libc::uintptr_t;
+        const EOPT_CURSORBEYONDEOL    = ffi::EDITOR_OPTIONS::EOPT_CURSORBEYONDEOL as libc::uintptr_t;
+        const EOPT_EXPANDONLYNEWTABS  = ffi::EDITOR_OPTIONS::EOPT_EXPANDONLYNEWTABS as libc::uintptr_t;
+        const EOPT_SHOWWHITESPACE     = ffi::EDITOR_OPTIONS::EOPT_SHOWWHITESPACE as libc::uintptr_t;
+        const EOPT_BOM                = ffi::EDITOR_OPTIONS::EOPT_BOM as libc::uintptr_t;
+        const EOPT_SHOWLINEBREAK      = ffi::EDITOR_OPTIONS::EOPT_SHOWLINEBREAK as libc::uintptr_t;
+        const EOPT_SHOWTITLEBAR       = ffi::EDITOR_OPTIONS::EOPT_SHOWTITLEBAR as libc::uintptr_t;
+        const EOPT_SHOWKEYBAR         = ffi::EDITOR_OPTIONS::EOPT_SHOWKEYBAR as libc::uintptr_t;
+        const EOPT_SHOWSCROLLBAR      = ffi::EDITOR_OPTIONS::EOPT_SHOWSCROLLBAR as libc::uintptr_t;
+    }
+}
+
+bitflags! {
+    #[allow(non_camel_case_types)]
+    pub struct EDITOR_CURRENTSTATE: libc::uintptr_t {
+        const ECSTATE_MODIFIED = ffi::EDITOR_CURRENTSTATE::ECSTATE_MODIFIED as libc::uintptr_t;
+        const ECSTATE_SAVED    = ffi::EDITOR_CURRENTSTATE::ECSTATE_SAVED as libc::uintptr_t;
+        const ECSTATE_LOCKED   = ffi::EDITOR_CURRENTSTATE::ECSTATE_LOCKED as libc::uintptr_t;
+    }
+}
+
+pub enum CharCodeBase {
+    Octal = 0,
+    Decimal = 1,
+    Hexadecimal = 2,
+}
+
+pub struct EditorInfo {
+    pub editor_id: libc::intptr_t,
+    pub window_size_x: libc::intptr_t,
+    pub window_size_y: libc::intptr_t,
+    pub total_lines: libc::intptr_t,
+    pub cur_line: libc::intptr_t,
+    pub cur_pos: libc::intptr_t,
+    pub cur_tab_pos: libc::intptr_t,
+    pub top_screen_line: libc::intptr_t,
+    pub left_pos: libc::intptr_t,
+    pub overtype: bool,
+    pub block_type: libc::intptr_t,
+    pub block_start_line: libc::intptr_t,
+    pub options: EDITOR_OPTIONS,
+    pub tab_size: libc::intptr_t,
+    pub bookmark_count: libc::size_t,
+    pub session_bookmark_count: libc::size_t,
+    pub cur_state: EDITOR_CURRENTSTATE,
+    pub code_page: libc::uintptr_t,
+}
+
+impl From<&ffi::EditorInfo> for EditorInfo {
+    fn from(info: &ffi::EditorInfo) -> Self {
+        EditorInfo {
+            editor_id: info.editor_id,
+            window_size_x: info.window_size_x,
+            window_size_y: info.window_size_y,
+            total_lines: info.total_lines,
+            cur_line: info.cur_line,
+            cur_pos: info.cur_pos,
+            cur_tab_pos: info.cur_tab_pos,
+            top_screen_line: info.top_screen_line,
+            left_pos: info.left_pos,
+            overtype: info.overtype != 0,
+            block_type: info.block_type,
+            block_start_line: info.block_start_line,
+            options: EDITOR_OPTIONS::from_bits_truncate(info.options),
+            tab_size: info.tab_size,
+            bookmark_count: info.bookmark_count,
+            session_bookmark_count: info.session_bookmark_count,
+            cur_state: EDITOR_CURRENTSTATE::from_bits_truncate(info.cur_state),
+            code_page: info.code_page,
+        }
+    }
+}
+
+// Line text is kept with its length as it may contain NUL characters.
+pub struct EditorString {
+    pub string_number: libc::intptr_t,
+    pub text: Vec<ffi::wchar_t>,
+    pub eol: WideString,
+    pub sel_start: libc::intptr_t,
+    pub sel_end: libc::intptr_t,
+}
+
+#[derive(Default)]
+pub struct EditorSetPosition {
+    pub cur_line: Option<usize>,
+    pub cur_pos: Option<usize>,
+    pub cur_tab_pos: Option<usize>,
+    pub top_screen_line: Option<usize>,
+    pub left_pos: Option<usize>,
+    pub overtype: Option<bool>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+// Stream end is exclusive, column block lines are inclusive and its columns are screen positions with
+// exclusive right bound.
+#[derive(Clone, Copy)]
+pub enum Selection {
+    None,
+    Stream {
+        start: Position,
+        end: Position,
+    },
+    Column {
+        top: usize,
+        left: usize,
+        bottom: usize,
+        right: usize,
+    },
+}
+
+pub struct SelectedLine {
+    pub line: usize,
+    pub text: Vec<ffi::wchar_t>,
+    pub eol: WideString,
+    pub start: usize,
+    pub end: usize,
+    pub eol_selected: bool,
+}
+
+impl SelectedLine {
+    pub fn selected_text(&self) -> &[ffi::wchar_t] {
+        &self.text[self.start..self.end]
+    }
+}
+
+pub struct SelectedLines {
+    editor: Editor,
+    line: usize,
+    total_lines: usize,
+}
+
+// Yields the error and ends if a line can't be read.
+impl Iterator for SelectedLines {
+    type Item = Result<SelectedLine>;
+
+    fn next(&mut self) -> Option<Result<SelectedLine>> {
+        if self.line >= self.total_lines {
+            return None;
+        }
+        let string = match self.editor.get_string(Some(self.line)) {
+            Ok(string) => string,
+            Err(err) => {
+                self.line = self.total_lines;
+                return Some(Err(err));
+            }
+        };
+        if string.sel_start == -1 {
+            self.line = self.total_lines;
+            return None;
+        }
+        self.line += 1;
+
+        let len = string.text.len();
+        let start = (string.sel_start as usize).min(len);
+        let end = match string.sel_end {
+            -1 => len,
+            sel_end => (sel_end as usize).min(len).max(start)
+        };
+        Some(Ok(SelectedLine {
+            line: string.string_number as usize,
+            eol_selected: string.sel_end == -1,
+            text: string.text,
+            eol: string.eol,
+            start,
+            end,
+        }))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Eol {
+    Keep,
+    CrLf,
+    Lf,
+    Cr,
+}
+
+impl Eol {
+    fn as_wide_string(&self) -> Option<WideString> {
+        match *self {
+            Eol::Keep => None,
+            Eol::CrLf => Some(WideString::from("\r\n")),
+            Eol::Lf => Some(WideString::from("\n")),
+            Eol::Cr => Some(WideString::from("\r")),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SaveError {
+    pub editor_id: libc::intptr_t,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Fail to save the file of the editor {}", self.editor_id)
+    }
+}
+
+impl Fail for SaveError {}
+
+#[derive(Clone, Copy)]
+pub struct Bookmark {
+    pub line: usize,
+    pub cursor: usize,
+    pub screen_line: usize,
+    pub left_pos: usize,
+}
+
+// Colors are deleted by owner, so every highlighter of the plugin should use its own layer.
+// Layer GUIDs are derived from the plugin GUID, layer 0 is the plugin GUID itself.
+#[derive(Clone, Copy)]
+pub struct ColorOwner {
+    guid: ffi::GUID
+}
+
+impl ColorOwner {
+
+    pub fn plugin() -> Self {
+        ColorOwner::layer(0)
+    }
+
+    pub fn layer(layer: u16) -> Self {
+        let mut guid = basic::plugin_guid();
+        let bytes = layer.to_be_bytes();
+        guid.Data4[6] ^= bytes[0];
+        guid.Data4[7] ^= bytes[1];
+        ColorOwner {
+            guid
+        }
+    }
+
+    pub fn guid(&self) -> &ffi::GUID {
+        &self.guid
+    }
+}
+
+pub struct EditorColor {
+    pub line: Option<usize>,
+    pub columns: Range<usize>,
+    pub color: FarColor,
+    pub priority: u32,
+    pub flags: EDITORCOLORFLAGS,
+    pub owner: ColorOwner,
+}
+
+impl EditorColor {
+    pub fn new(line: usize, columns: Range<usize>, color: FarColor, owner: ColorOwner) -> Self {
+        EditorColor {
+            line: Some(line),
+            columns,
+            color,
+            priority: ffi::EDITOR_COLOR_NORMAL_PRIORITY,
+            flags: EDITORCOLORFLAGS::ECF_NONE,
+            owner,
+        }
+    }
+
+    // Far deletes the color itself once the line is drawn, so it needs no clear_colors on redraw.
+    pub fn auto_delete(line: usize, columns: Range<usize>, color: FarColor, owner: ColorOwner) -> Self {
+        EditorColor {
+            flags: EDITORCOLORFLAGS::ECF_AUTODELETE,
+            ..EditorColor::new(line, columns, color, owner)
+        }
+    }
+}
+
+impl Editor {
+
+    pub fn current() -> Editor {
+        Editor::from(ffi::CURRENT_EDITOR as libc::intptr_t)
+    }
+
+    pub fn id(&self) -> libc::intptr_t {
+        self.id
+    }
+
+    pub fn get_info(&self) -> Result<EditorInfo> {
+        trace!(">get_info()");
+        let mut info: ffi::EditorInfo = unsafe { mem::zeroed() };
+        info.struct_size = mem::size_of::<ffi::EditorInfo>();
+
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_GETINFO,
+                                         0,
+                                         &mut info as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(EditorInfo::from(&info))
+        };
+        trace!("<get_info()");
+        return result;
+    }
+
+    pub fn get_string(&self, line: Option<usize>) -> Result<EditorString> {
+        trace!(">get_string()");
+        let mut egs = ffi::EditorGetString {
+            struct_size: mem::size_of::<ffi::EditorGetString>(),
+            string_number: line.map_or(-1, |l| l as libc::intptr_t),
+            string_length: 0,
+            string_text: ptr::null(),
+            string_eol: ptr::null(),
+            sel_start: 0,
+            sel_end: 0,
+        };
+
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_GETSTRING,
+                                         0,
+                                         &mut egs as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => {
+                let text = if egs.string_text != ptr::null() {
+                    unsafe { slice::from_raw_parts(egs.string_text, egs.string_length as usize) }.to_vec()
+                } else {
+                    Vec::new()
+                };
+                let eol = if egs.string_eol != ptr::null() {
+                    unsafe { WideString::from_ptr_str(egs.string_eol) }
+                } else {
+                    WideString::new()
+                };
+                Ok(EditorString {
+                    string_number: egs.string_number,
+                    text,
+                    eol,
+                    sel_start: egs.sel_start,
+                    sel_end: egs.sel_end,
+                })
+            }
+        };
+        trace!("<get_string()");
+        return result;
+    }
+
+    pub fn set_string(&self, line: Option<usize>, text: &[ffi::wchar_t], eol: Option<&WideString>) -> Result<()> {
+        trace!(">set_string()");
+        let mut ess = ffi::EditorSetString {
+            struct_size: mem::size_of::<ffi::EditorSetString>(),
+            string_number: line.map_or(-1, |l| l as libc::intptr_t),
+            string_length: text.len() as libc::intptr_t,
+            string_text: text.as_ptr(),
+            string_eol: eol.map_or(ptr::null(), |s| s.as_ptr()),
+        };
+
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_SETSTRING,
+                                         0,
+                                         &mut ess as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<set_string()");
+        return result;
+    }
+
+    pub fn set_position(&self, position: EditorSetPosition) -> Result<()> {
+        trace!(">set_position()");
+        let mut esp = ffi::EditorSetPosition {
+            struct_size: mem::size_of::<ffi::EditorSetPosition>(),
+            cur_line: position.cur_line.map_or(-1, |v| v as libc::intptr_t),
+            cur_pos: position.cur_pos.map_or(-1, |v| v as libc::intptr_t),
+            cur_tab_pos: position.cur_tab_pos.map_or(-1, |v| v as libc::intptr_t),
+            top_screen_line: position.top_screen_line.map_or(-1, |v| v as libc::intptr_t),
+            left_pos: position.left_pos.map_or(-1, |v| v as libc::intptr_t),
+            overtype: position.overtype.map_or(-1, |v| if v { 1 } else { 0 }),
+        };
+
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_SETPOSITION,
+                                         0,
+                                         &mut esp as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<set_position()");
+        return result;
+    }
+
+    pub fn set_cursor(&self, line: usize, pos: usize) -> Result<()> {
+        self.set_position(EditorSetPosition {
+            cur_line: Some(line),
+            cur_pos: Some(pos),
+            ..Default::default()
+        })
+    }
+
+    pub fn insert_text(&self, text: &WideString) -> Result<()> {
+        trace!(">insert_text()");
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_INSERTTEXT,
+                                         0,
+                                         text.as_ptr() as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<insert_text()");
+        return result;
+    }
+
+    pub fn insert_string(&self, indent: bool) -> Result<()> {
+        trace!(">insert_string()");
+        let mut indent: libc::c_int = if indent { 1 } else { 0 };
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_INSERTSTRING,
+                                         0,
+                                         &mut indent as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<insert_string()");
+        return result;
+    }
+
+    pub fn delete_string(&self) -> Result<()> {
+        trace!(">delete_string()");
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_DELETESTRING,
+                                         0,
+                                         ptr::null_mut()) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<delete_string()");
+        return result;
+    }
+
+    pub fn delete_char(&self) -> Result<()> {
+        trace!(">delete_char()");
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_DELETECHAR,
+                                         0,
+                                         ptr::null_mut()) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<delete_char()");
+        return result;
+    }
+
+    pub fn delete_block(&self) -> Result<()> {
+        trace!(">delete_block()");
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_DELETEBLOCK,
+                                         0,
+                                         ptr::null_mut()) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<delete_block()");
+        return result;
+    }
+
+    pub fn get_selection(&self) -> Result<Selection> {
+        trace!(">get_selection()");
+        let info = self.get_info()?;
+
+        let block_type = match info.block_type {
+            t if t == EDITOR_BLOCK_TYPES::BTYPE_STREAM as libc::intptr_t => EDITOR_BLOCK_TYPES::BTYPE_STREAM,
+            t if t == EDITOR_BLOCK_TYPES::BTYPE_COLUMN as libc::intptr_t => EDITOR_BLOCK_TYPES::BTYPE_COLUMN,
+            _ => {
+                trace!("<get_selection()");
+                return Ok(Selection::None);
+            }
+        };
+
+        let top = info.block_start_line as usize;
+        let first = self.get_string(Some(top))?;
+        let mut bottom = top;
+        let mut last_sel_end = first.sel_end;
+        let mut last_length = first.text.len();
+        let mut line = top + 1;
+        while line < info.total_lines as usize {
+            let string = self.get_string(Some(line))?;
+            if string.sel_start == -1 {
+                break;
+            }
+            bottom = line;
+            last_sel_end = string.sel_end;
+            last_length = string.text.len();
+            line += 1;
+        }
+
+        let result = match block_type {
+            EDITOR_BLOCK_TYPES::BTYPE_COLUMN => Selection::Column {
+                top,
+                left: self.real_to_tab(Some(top), first.sel_start as usize)?,
+                bottom,
+                right: self.real_to_tab(Some(top), first.sel_end as usize)?,
+            },
+            _ => Selection::Stream {
+                start: Position {
+                    line: top,
+                    column: first.sel_start as usize,
+                },
+                end: match last_sel_end {
+                    // the line break of the last line of the file ends at the line length
+                    -1 if bottom + 1 < info.total_lines as usize => Position {
+                        line: bottom + 1,
+                        column: 0,
+                    },
+                    -1 => Position {
+                        line: bottom,
+                        column: last_length,
+                    },
+                    sel_end => Position {
+                        line: bottom,
+                        column: sel_end as usize,
+                    }
+                },
+            },
+        };
+        trace!("<get_selection()");
+        return Ok(result);
+    }
+
+    pub fn set_selection(&self, selection: &Selection) -> Result<()> {
+        trace!(">set_selection()");
+        let mut es = ffi::EditorSelect {
+            struct_size: mem::size_of::<ffi::EditorSelect>(),
+            block_type: EDITOR_BLOCK_TYPES::BTYPE_NONE as libc::intptr_t,
+            block_start_line: 0,
+            block_start_pos: 0,
+            block_width: 0,
+            block_height: 0,
+        };
+        match *selection {
+            Selection::None => {},
+            Selection::Stream { start, end } => {
+                // reversed selection is selected from its end
+                let (start, end) = match (end.line, end.column) < (start.line, start.column) {
+                    true => (end, start),
+                    false => (start, end)
+                };
+                es.block_type = EDITOR_BLOCK_TYPES::BTYPE_STREAM as libc::intptr_t;
+                es.block_start_line = start.line as libc::intptr_t;
+                es.block_start_pos = start.column as libc::intptr_t;
+                es.block_height = (end.line - start.line + 1) as libc::intptr_t;
+                es.block_width = match end.line == start.line {
+                    true => (end.column - start.column) as libc::intptr_t,
+                    false => end.column as libc::intptr_t,
+                };
+            },
+            Selection::Column { top, left, bottom, right } => {
+                let (top, bottom) = (top.min(bottom), top.max(bottom));
+                let (left, right) = (left.min(right), left.max(right));
+                es.block_type = EDITOR_BLOCK_TYPES::BTYPE_COLUMN as libc::intptr_t;
+                es.block_start_line = top as libc::intptr_t;
+                es.block_start_pos = left as libc::intptr_t;
+                es.block_height = (bottom - top + 1) as libc::intptr_t;
+                es.block_width = (right - left) as libc::intptr_t;
+            },
+        }
+
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_SELECT,
+                                         0,
+                                         &mut es as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<set_selection()");
+        return result;
+    }
+
+    pub fn selected_lines(&self) -> Result<SelectedLines> {
+        trace!(">selected_lines()");
+        let info = self.get_info()?;
+        let line = match info.block_type {
+            t if t == EDITOR_BLOCK_TYPES::BTYPE_NONE as libc::intptr_t => info.total_lines as usize,
+            _ => info.block_start_line as usize,
+        };
+        trace!("<selected_lines()");
+        return Ok(SelectedLines {
+            editor: *self,
+            line,
+            total_lines: info.total_lines as usize,
+        });
+    }
+
+    pub fn real_to_tab(&self, line: Option<usize>, pos: usize) -> Result<usize> {
+        trace!(">real_to_tab()");
+        let result = self.convert_pos(ffi::EDITOR_CONTROL_COMMANDS::ECTL_REALTOTAB, line, pos);
+        trace!("<real_to_tab()");
+        return result;
+    }
+
+    pub fn tab_to_real(&self, line: Option<usize>, pos: usize) -> Result<usize> {
+        trace!(">tab_to_real()");
+        let result = self.convert_pos(ffi::EDITOR_CONTROL_COMMANDS::ECTL_TABTOREAL, line, pos);
+        trace!("<tab_to_real()");
+        return result;
+    }
+
+    fn convert_pos(&self, command: ffi::EDITOR_CONTROL_COMMANDS, line: Option<usize>, pos: usize) -> Result<usize> {
+        let mut ecp = ffi::EditorConvertPos {
+            struct_size: mem::size_of::<ffi::EditorConvertPos>(),
+            string_number: line.map_or(-1, |l| l as libc::intptr_t),
+            src_pos: pos as libc::intptr_t,
+            dest_pos: 0,
+        };
+
+        match self.control(command,
+                           0,
+                           &mut ecp as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(ecp.dest_pos as usize)
+        }
+    }
+
+    pub fn add_color(&self, color: &EditorColor) -> Result<()> {
+        trace!(">add_color()");
+        if color.columns.start >= color.columns.end {
+            trace!("<add_color()");
+            return Ok(());
+        }
+        let mut ec = ffi::EditorColor {
+            struct_size: mem::size_of::<ffi::EditorColor>(),
+            string_number: color.line.map_or(-1, |l| l as libc::intptr_t),
+            color_item: 0,
+            start_pos: color.columns.start as libc::intptr_t,
+            end_pos: (color.columns.end - 1) as libc::intptr_t,
+            priority: color.priority as libc::uintptr_t,
+            flags: color.flags,
+            color: color.color,
+            owner: color.owner.guid,
+        };
+
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_ADDCOLOR,
+                                         0,
+                                         &mut ec as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<add_color()");
+        return result;
+    }
+
+    pub fn delete_color(&self, owner: &ColorOwner, line: Option<usize>, start_pos: Option<usize>) -> Result<()> {
+        trace!(">delete_color()");
+        let mut edc = ffi::EditorDeleteColor {
+            struct_size: mem::size_of::<ffi::EditorDeleteColor>(),
+            owner: owner.guid,
+            string_number: line.map_or(-1, |l| l as libc::intptr_t),
+            start_pos: start_pos.map_or(-1, |p| p as libc::intptr_t),
+        };
+
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_DELCOLOR,
+                                         0,
+                                         &mut edc as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<delete_color()");
+        return result;
+    }
+
+    // Deletes the colors of the owner from every line of the file.
+    pub fn clear_colors(&self, owner: &ColorOwner) -> Result<()> {
+        trace!(">clear_colors()");
+        let info = self.get_info()?;
+        for line in 0..info.total_lines.max(0) as usize {
+            self.delete_color(owner, Some(line), None)?;
+        }
+        trace!("<clear_colors()");
+        return Ok(());
+    }
+
+    // Deletes the colors of the owner from the lines shown in the window, so it is cheap enough to be
+    // called on every redraw. Colors of the other lines are kept.
+    pub fn clear_visible_colors(&self, owner: &ColorOwner) -> Result<()> {
+        trace!(">clear_visible_colors()");
+        let info = self.get_info()?;
+        let top = info.top_screen_line.max(0) as usize;
+        let bottom = (top + info.window_size_y.max(0) as usize).min(info.total_lines.max(0) as usize);
+        for line in top..bottom {
+            self.delete_color(owner, Some(line), None)?;
+        }
+        trace!("<clear_visible_colors()");
+        return Ok(());
+    }
+
+    pub fn subscribe_change_event(&self) -> Result<()> {
+        trace!(">subscribe_change_event()");
+        let mut esce = ffi::EditorSubscribeChangeEvent {
+            struct_size: mem::size_of::<ffi::EditorSubscribeChangeEvent>(),
+            plugin_id: basic::plugin_guid(),
+        };
+
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_SUBSCRIBECHANGEEVENT,
+                                         0,
+                                         &mut esce as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<subscribe_change_event()");
+        return result;
+    }
+
+    pub fn unsubscribe_change_event(&self) -> Result<()> {
+        trace!(">unsubscribe_change_event()");
+        let mut esce = ffi::EditorSubscribeChangeEvent {
+            struct_size: mem::size_of::<ffi::EditorSubscribeChangeEvent>(),
+            plugin_id: basic::plugin_guid(),
+        };
+
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_UNSUBSCRIBECHANGEEVENT,
+                                         0,
+                                         &mut esce as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<unsubscribe_change_event()");
+        return result;
+    }
+
+    pub fn set_tab_size(&self, tab_size: usize) -> Result<()> {
+        trace!(">set_tab_size()");
+        let result = self.set_int_param(ffi::EDITOR_SETPARAMETER_TYPES::ESPT_TABSIZE, tab_size as libc::intptr_t);
+        trace!("<set_tab_size()");
+        return result;
+    }
+
+    pub fn set_expand_tabs(&self, mode: EXPAND_TABS) -> Result<()> {
+        trace!(">set_expand_tabs()");
+        let result = self.set_int_param(ffi::EDITOR_SETPARAMETER_TYPES::ESPT_EXPANDTABS, mode as libc::intptr_t);
+        trace!("<set_expand_tabs()");
+        return result;
+    }
+
+    pub fn set_auto_indent(&self, enabled: bool) -> Result<()> {
+        trace!(">set_auto_indent()");
+        let result = self.set_int_param(ffi::EDITOR_SETPARAMETER_TYPES::ESPT_AUTOINDENT, enabled as libc::intptr_t);
+        trace!("<set_auto_indent()");
+        return result;
+    }
+
+    pub fn set_cursor_beyond_eol(&self, enabled: bool) -> Result<()> {
+        trace!(">set_cursor_beyond_eol()");
+        let result = self.set_int_param(ffi::EDITOR_SETPARAMETER_TYPES::ESPT_CURSORBEYONDEOL, enabled as libc::intptr_t);
+        trace!("<set_cursor_beyond_eol()");
+        return result;
+    }
+
+    pub fn set_char_code_base(&self, base: CharCodeBase) -> Result<()> {
+        trace!(">set_char_code_base()");
+        let result = self.set_int_param(ffi::EDITOR_SETPARAMETER_TYPES::ESPT_CHARCODEBASE, base as libc::intptr_t);
+        trace!("<set_char_code_base()");
+        return result;
+    }
+
+    pub fn set_code_page(&self, code_page: libc::uintptr_t) -> Result<()> {
+        trace!(">set_code_page()");
+        let result = self.set_int_param(ffi::EDITOR_SETPARAMETER_TYPES::ESPT_CODEPAGE, code_page as libc::intptr_t);
+        trace!("<set_code_page()");
+        return result;
+    }
+
+    pub fn set_lock_mode(&self, locked: bool) -> Result<()> {
+        trace!(">set_lock_mode()");
+        let result = self.set_int_param(ffi::EDITOR_SETPARAMETER_TYPES::ESPT_LOCKMODE, locked as libc::intptr_t);
+        trace!("<set_lock_mode()");
+        return result;
+    }
+
+    pub fn set_show_whitespace(&self, show: bool) -> Result<()> {
+        trace!(">set_show_whitespace()");
+        let result = self.set_int_param(ffi::EDITOR_SETPARAMETER_TYPES::ESPT_SHOWWHITESPACE, show as libc::intptr_t);
+        trace!("<set_show_whitespace()");
+        return result;
+    }
+
+    pub fn set_word_divisors(&self, divisors: &WideString) -> Result<()> {
+        trace!(">set_word_divisors()");
+        let mut esp = ffi::EditorSetParameter {
+            struct_size: mem::size_of::<ffi::EditorSetParameter>(),
+            parameter_type: ffi::EDITOR_SETPARAMETER_TYPES::ESPT_SETWORDDIV,
+            param: ffi::EditorSetParameterParam { wsz_param: divisors.as_ptr() as *mut ffi::wchar_t },
+            flags: 0,
+            size: 0,
+        };
+
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_SETPARAM,
+                                         0,
+                                         &mut esp as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<set_word_divisors()");
+        return result;
+    }
+
+    pub fn get_word_divisors(&self) -> Result<WideString> {
+        trace!(">get_word_divisors()");
+        let mut esp = ffi::EditorSetParameter {
+            struct_size: mem::size_of::<ffi::EditorSetParameter>(),
+            parameter_type: ffi::EDITOR_SETPARAMETER_TYPES::ESPT_GETWORDDIV,
+            param: ffi::EditorSetParameterParam { wsz_param: ptr::null_mut() },
+            flags: 0,
+            size: 0,
+        };
+
+        let size = self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_SETPARAM,
+                                0,
+                                &mut esp as *mut _ as *mut libc::c_void);
+        if size <= 0 {
+            trace!("<get_word_divisors()");
+            return Err(format_err!(""));
+        }
+
+        let mut buf: Vec<ffi::wchar_t> = vec![0; size as usize];
+        esp.param = ffi::EditorSetParameterParam { wsz_param: buf.as_mut_ptr() };
+        esp.size = buf.len();
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_SETPARAM,
+                                         0,
+                                         &mut esp as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(WideString::from(buf.as_slice()))
+        };
+        trace!("<get_word_divisors()");
+        return result;
+    }
+
+    fn set_int_param(&self, parameter_type: ffi::EDITOR_SETPARAMETER_TYPES, value: libc::intptr_t) -> Result<()> {
+        let mut esp = ffi::EditorSetParameter {
+            struct_size: mem::size_of::<ffi::EditorSetParameter>(),
+            parameter_type,
+            param: ffi::EditorSetParameterParam { i_param: value },
+            flags: 0,
+            size: 0,
+        };
+
+        match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_SETPARAM,
+                           0,
+                           &mut esp as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        }
+    }
+
+    // Slots of the standard bookmarks are kept, not set ones are None
+    pub fn get_bookmarks(&self) -> Result<Vec<Option<Bookmark>>> {
+        trace!(">get_bookmarks()");
+        let result = self.bookmarks(ffi::EDITOR_CONTROL_COMMANDS::ECTL_GETBOOKMARKS)
+            .map(|bookmarks| bookmarks.into_iter()
+                .map(|(line, bookmark)| if line < 0 { None } else { Some(bookmark) })
+                .collect());
+        trace!("<get_bookmarks()");
+        return result;
+    }
+
+    pub fn get_session_bookmarks(&self) -> Result<Vec<Bookmark>> {
+        trace!(">get_session_bookmarks()");
+        let result = self.bookmarks(ffi::EDITOR_CONTROL_COMMANDS::ECTL_GETSESSIONBOOKMARKS)
+            .map(|bookmarks| bookmarks.into_iter().map(|(_, bookmark)| bookmark).collect());
+        trace!("<get_session_bookmarks()");
+        return result;
+    }
+
+    pub fn add_session_bookmark(&self) -> Result<()> {
+        trace!(">add_session_bookmark()");
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_ADDSESSIONBOOKMARK,
+                                         0,
+                                         ptr::null_mut()) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<add_session_bookmark()");
+        return result;
+    }
+
+    // None deletes the current session bookmark
+    pub fn delete_session_bookmark(&self, index: Option<usize>) -> Result<()> {
+        trace!(">delete_session_bookmark()");
+        let index: libc::intptr_t = index.map_or(-1, |i| i as libc::intptr_t);
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_DELETESESSIONBOOKMARK,
+                                         0,
+                                         index as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<delete_session_bookmark()");
+        return result;
+    }
+
+    pub fn clear_session_bookmarks(&self) -> Result<()> {
+        trace!(">clear_session_bookmarks()");
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_CLEARSESSIONBOOKMARKS,
+                                         0,
+                                         ptr::null_mut()) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<clear_session_bookmarks()");
+        return result;
+    }
+
+    pub fn next_session_bookmark(&self) -> Result<()> {
+        trace!(">next_session_bookmark()");
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_NEXTSESSIONBOOKMARK,
+                                         0,
+                                         ptr::null_mut()) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<next_session_bookmark()");
+        return result;
+    }
+
+    pub fn prev_session_bookmark(&self) -> Result<()> {
+        trace!(">prev_session_bookmark()");
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_PREVSESSIONBOOKMARK,
+                                         0,
+                                         ptr::null_mut()) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<prev_session_bookmark()");
+        return result;
+    }
+
+    pub fn goto_bookmark(&self, bookmark: &Bookmark) -> Result<()> {
+        trace!(">goto_bookmark()");
+        let result = self.set_position(EditorSetPosition {
+            cur_line: Some(bookmark.line),
+            cur_pos: Some(bookmark.cursor),
+            top_screen_line: Some(bookmark.line.saturating_sub(bookmark.screen_line)),
+            left_pos: Some(bookmark.left_pos),
+            ..Default::default()
+        });
+        trace!("<goto_bookmark()");
+        return result;
+    }
+
+    fn bookmarks(&self, command: ffi::EDITOR_CONTROL_COMMANDS) -> Result<Vec<(libc::intptr_t, Bookmark)>> {
+        // Far Manager places the arrays right after the structure, in the buffer of the requested size
+        let size = self.control(command, 0, ptr::null_mut());
+        if size < mem::size_of::<ffi::EditorBookmarks>() as libc::intptr_t {
+            return Err(format_err!(""));
+        }
+
+        let mut buf: Vec<libc::intptr_t> = vec![0; (size as usize + mem::size_of::<libc::intptr_t>() - 1) / mem::size_of::<libc::intptr_t>()];
+        let ebm = buf.as_mut_ptr() as *mut ffi::EditorBookmarks;
+        unsafe {
+            (*ebm).struct_size = mem::size_of::<ffi::EditorBookmarks>();
+            (*ebm).size = size as libc::size_t;
+        }
+        if self.control(command, 0, ebm as *mut libc::c_void) == 0 {
+            return Err(format_err!(""));
+        }
+
+        let ebm = unsafe { &*ebm };
+        let mut result = Vec::with_capacity(ebm.count);
+        for i in 0..ebm.count {
+            let (line, cursor, screen_line, left_pos) = unsafe {
+                (*ebm.line.add(i), *ebm.cursor.add(i), *ebm.screen_line.add(i), *ebm.left_pos.add(i))
+            };
+            result.push((line, Bookmark {
+                line: line.max(0) as usize,
+                cursor: cursor.max(0) as usize,
+                screen_line: screen_line.max(0) as usize,
+                left_pos: left_pos.max(0) as usize,
+            }));
+        }
+        return Ok(result);
+    }
+
+    pub fn save(&self, path: Option<&WideString>, eol: Eol, code_page: Option<u32>) -> result::Result<(), SaveError> {
+        trace!(">save()");
+        let eol = eol.as_wide_string();
+        let mut esf = ffi::EditorSaveFile {
+            struct_size: mem::size_of::<ffi::EditorSaveFile>(),
+            file_name: path.map_or(ptr::null(), |p| p.as_ptr()),
+            file_eol: eol.as_ref().map_or(ptr::null(), |e| e.as_ptr()),
+            code_page: code_page.map_or(ffi::CP_DEFAULT, |cp| cp as libc::uintptr_t),
+        };
+
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_SAVEFILE,
+                                         0,
+                                         &mut esf as *mut _ as *mut libc::c_void) {
+            0 => Err(SaveError { editor_id: self.id }),
+            _ => Ok(())
+        };
+        trace!("<save()");
+        return result;
+    }
+
+    pub fn begin_undo_group(&self) -> Result<UndoGroup> {
+        trace!(">begin_undo_group()");
+        let result = self.undo_redo(ffi::EDITOR_UNDOREDO_COMMANDS::EUR_BEGIN)
+            .map(|_| UndoGroup { editor: *self });
+        trace!("<begin_undo_group()");
+        return result;
+    }
+
+    pub fn undo(&self) -> Result<()> {
+        self.undo_redo(ffi::EDITOR_UNDOREDO_COMMANDS::EUR_UNDO)
+    }
+
+    pub fn redo(&self) -> Result<()> {
+        self.undo_redo(ffi::EDITOR_UNDOREDO_COMMANDS::EUR_REDO)
+    }
+
+    fn undo_redo(&self, command: ffi::EDITOR_UNDOREDO_COMMANDS) -> Result<()> {
+        let mut eur = ffi::EditorUndoRedo {
+            struct_size: mem::size_of::<ffi::EditorUndoRedo>(),
+            command,
+        };
+
+        match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_UNDOREDO,
+                           0,
+                           &mut eur as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        }
+    }
+
+    pub fn redraw(&self) -> Result<()> {
+        trace!(">redraw()");
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_REDRAW,
+                                         0,
+                                         ptr::null_mut()) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<redraw()");
+        return result;
+    }
+
+    pub fn quit(&self) -> Result<()> {
+        trace!(">quit()");
+        let result = match self.control(ffi::EDITOR_CONTROL_COMMANDS::ECTL_QUIT,
+                                         0,
+                                         ptr::null_mut()) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<quit()");
+        return result;
+    }
+
+    fn control(&self, command: ffi::EDITOR_CONTROL_COMMANDS, param1: libc::intptr_t, param2: *mut libc::c_void) -> libc::intptr_t {
+        far_api(|far_api: &mut ffi::PluginStartupInfo| {
+            far_api.editor_control(self.id, command, param1, param2)
+        })
+    }
+}
+
+#[must_use]
+pub struct UndoGroup {
+    editor: Editor
+}
+
+impl Drop for UndoGroup {
+    fn drop(&mut self) {
+        let _ = self.editor.undo_redo(ffi::EDITOR_UNDOREDO_COMMANDS::EUR_END);
+    }
+}
+
+// Sessions of each instance are dropped after the EE_CLOSE event is processed by ProcessEditorEventW,
+// so they need the editor feature.
+#[cfg(feature = "editor")]
+pub struct EditorSessions<T: 'static> {
+    sessions: ctx::Sessions<T>,
+    registered: Cell<bool>,
+}
+
+#[cfg(feature = "editor")]
+impl<T: Default + 'static> Default for EditorSessions<T> {
+    fn default() -> Self {
+        EditorSessions::new()
+    }
+}
+
+#[cfg(feature = "editor")]
+impl<T: Default + 'static> EditorSessions<T> {
+
+    pub fn new() -> Self {
+        EditorSessions {
+            sessions: ctx::Sessions::default(),
+            registered: Cell::new(false),
+        }
+    }
+
+    // The session is taken out of the store while func runs, so func may use the store again.
+    // A nested call for the same editor gets a new session, which is replaced when func returns.
+    pub fn with<F, R>(&self, editor_id: libc::intptr_t, func: F) -> R where F: FnOnce(&mut T) -> R {
+        if !self.registered.replace(true) {
+            context(|ctx: &mut ctx::Context| ctx.register_sessions(&self.sessions));
+        }
+        let taken = self.sessions.borrow_mut().insert(editor_id, None);
+        let mut session = taken.and_then(|session| session).unwrap_or_default();
+        let result = func(&mut session);
+        // the session isn't put back if the editor was closed or the session removed meanwhile
+        let closed = match self.sessions.borrow_mut().get_mut(&editor_id) {
+            Some(slot) => {
+                *slot = Some(session);
+                None
+            },
+            None => Some(session)
+        };
+        drop(closed);
+        result
+    }
+
+    pub fn contains(&self, editor_id: libc::intptr_t) -> bool {
+        self.sessions.borrow().contains_key(&editor_id)
+    }
+
+    // Returns None if there is no session or it is used by with() at the moment.
+    pub fn remove(&self, editor_id: libc::intptr_t) -> Option<T> {
+        let removed = self.sessions.borrow_mut().remove(&editor_id);
+        removed.and_then(|session| session)
+    }
+}
+
+pub(crate) fn init_context() {
+    CONTEXT.with(|ref_cell: &RefCell<Option<ctx::Context>>| {
+        ref_cell.replace(Some(ctx::Context::default()));
+    });
+}
+
+#[allow(dead_code)]
+fn context<F,R>(func: F) -> R where F: FnOnce(&mut ctx::Context) -> R {
+    CONTEXT.with(|ref_cell: &RefCell<Option<ctx::Context>>| {
+        return match ref_cell.try_borrow_mut() {
+            Ok(mut r) => {
+                match *r {
+                    Some(ref mut ctx) => {
+                        func(ctx)
+                    },
+                    None => {
+                        panic!("Plugin is not initialized")
+                    }
+                }
+            },
+            Err(_) => {
+                panic!("Fail to acquire Editor API context")
+            }
+        };
+    })
+}
+
+pub(crate) fn cleanup_context() {
+    CONTEXT.with(|ref_cell: &RefCell<Option<ctx::Context>>| {
+        ref_cell.replace(None);
+    });
+}
+
+pub enum EditorExitCode {
+    OpenError,
+    Modified,
+    NotModified,
+    LoadingInterrupted,
+}
+
+// editor is known only for the editors opened with EF_NONMODAL and EF_IMMEDIATERETURN, otherwise Far
+// returns after the editor window loop and the current editor is not the opened one.
+pub struct EditorOpenResult {
+    pub exit_code: EditorExitCode,
+    pub editor: Option<Editor>,
+}
+
+pub struct EditorOpenOptions {
+    file_name: WideString,
+    title: Option<WideString>,
+    x1: libc::intptr_t,
+    y1: libc::intptr_t,
+    x2: libc::intptr_t,
+    y2: libc::intptr_t,
+    flags: EDITOR_FLAGS,
+    start_line: libc::intptr_t,
+    start_char: libc::intptr_t,
+    code_page: libc::uintptr_t,
+}
+
+impl EditorOpenOptions {
+
+    pub fn new(file_name: WideString) -> Self {
+        EditorOpenOptions {
+            file_name,
+            title: None,
+            x1: 0,
+            y1: 0,
+            x2: -1,
+            y2: -1,
+            flags: EDITOR_FLAGS::EN_NONE,
+            start_line: -1,
+            start_char: -1,
+            code_page: ffi::CP_DEFAULT,
+        }
+    }
+
+    pub fn title(mut self, title: WideString) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    pub fn rect(mut self, x1: isize, y1: isize, x2: isize, y2: isize) -> Self {
+        self.x1 = x1;
+        self.y1 = y1;
+        self.x2 = x2;
+        self.y2 = y2;
+        self
+    }
+
+    pub fn flags(mut self, flags: EDITOR_FLAGS) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    // line and column are 1-based, as in the Far Manager Editor() service function
+    pub fn position(mut self, line: usize, column: usize) -> Self {
+        self.start_line = line as libc::intptr_t;
+        self.start_char = column as libc::intptr_t;
+        self
+    }
+
+    pub fn code_page(mut self, code_page: libc::uintptr_t) -> Self {
+        self.code_page = code_page;
+        self
+    }
+
+    pub fn open(self) -> Result<EditorOpenResult> {
+        trace!(">open()");
+        let return_code = far_api(|far_api: &mut ffi::PluginStartupInfo| {
+            far_api.editor(self.file_name.as_ptr(),
+                           self.title.as_ref().map_or(ptr::null(), |t| t.as_ptr()),
+                           self.x1, self.y1, self.x2, self.y2,
+                           self.flags,
+                           self.start_line, self.start_char,
+                           self.code_page)
+        });
+
+        let exit_code = match return_code {
+            c if c == ffi::EDITOR_EXITCODE::EEC_OPEN_ERROR as libc::intptr_t => EditorExitCode::OpenError,
+            c if c == ffi::EDITOR_EXITCODE::EEC_MODIFIED as libc::intptr_t => EditorExitCode::Modified,
+            c if c == ffi::EDITOR_EXITCODE::EEC_NOT_MODIFIED as libc::intptr_t => EditorExitCode::NotModified,
+            c if c == ffi::EDITOR_EXITCODE::EEC_LOADING_INTERRUPTED as libc::intptr_t => EditorExitCode::LoadingInterrupted,
+            _ => {
+                trace!("<open()");
+                return Err(format_err!(""));
+            }
+        };
+
+        let immediate_return = EDITOR_FLAGS::EF_NONMODAL | EDITOR_FLAGS::EF_IMMEDIATERETURN;
+        let editor = match exit_code {
+            EditorExitCode::OpenError => None,
+            _ if self.flags.contains(immediate_return) => Editor::current().get_info().ok()
+                .map(|info| Editor::from(info.editor_id)),
+            _ => None
+        };
+        trace!("<open()");
+        return Ok(EditorOpenResult {
+            exit_code,
+            editor,
+        });
+    }
+}
+
+#[allow(unused_variables)]
+#[cfg(feature = "editor")]
+#[no_mangle]
+#[export_name="ProcessEditorEventW"]
+pub extern "system" fn process_editor_event(info: *const ffi::ProcessEditorEventInfo) -> libc::intptr_t {
+    trace!(">process_editor_event()");
+    let _ = panic::catch_unwind(|| {
+        let info_ref = unsafe { &*info };
+        assert_eq!(info_ref.struct_size, mem::size_of::<ffi::ProcessEditorEventInfo>());
+
+        if let Some(process_editor_event_info) = ProcessEditorEventInfo::from(info_ref) {
+            // plugins without editor exports still get their EditorSessions cleaned up below
+            plugin(|plugin: &mut dyn FarPlugin| {
+                if let Some(exports) = plugin.editor_exports() {
+                    exports.process_editor_event(process_editor_event_info);
+                }
+            });
+        }
+    });
+    let _ = panic::catch_unwind(|| {
+        let info_ref = unsafe { &*info };
+        if info_ref.event == ffi::EDITOR_EVENTS::EE_CLOSE as libc::intptr_t {
+            let closed_sessions = context(|ctx: &mut ctx::Context| ctx.close_editor(info_ref.editor_id));
+            drop(closed_sessions);
+        }
+    });
+    trace!("<process_editor_event()");
+    return 0;
+}
+
+#[allow(unused_variables)]
+#[cfg(feature = "editor")]
+#[no_mangle]
+#[export_name="ProcessEditorInputW"]
+pub extern "system" fn process_editor_input(info: *const ffi::ProcessEditorInputInfo) -> libc::intptr_t {
+    trace!(">process_editor_input()");
+    let call_result = panic::catch_unwind(|| {
+        let info_ref = unsafe { &*info };
+        assert_eq!(info_ref.struct_size, mem::size_of::<ffi::ProcessEditorInputInfo>());
 
-// TODO review and update
-pub fn open_editor(path: WideString) {
-    trace!(">open_editor()");
-    far_api(|far_api: &mut ffi::PluginStartupInfo| {
-        let file_name = path;
-        far_api.editor(file_name.as_ptr(), 0 as *const ffi::wchar_t, 0, 0, -1, -1, ffi::EDITOR_FLAGS::EN_NONE, 1, 1, ffi::CP_DEFAULT);
+        return match InputEvent::from(&info_ref.rec) {
+            Some(event) => plugin(|plugin: &mut dyn FarPlugin| {
+                match plugin.editor_exports() {
+                    Some(exports) => exports.process_editor_input(ProcessEditorInputInfo { event }),
+                    None => false
+                }
+            }),
+            None => false
+        };
     });
-    trace!("<open_editor()");
+    let r_val: libc::intptr_t = match call_result {
+        Ok(true) => 1,
+        Ok(false) => 0,
+        Err(_) => 0
+    };
+    trace!("<process_editor_input()");
+    return r_val;
 }
@@ -0,0 +1,123 @@
+use log::*;
+use winapi::um::wincon;
+use winapi::um::winuser;
+
+use crate::basic;
+
+use super::*;
+
+fn is_word_char(c: ffi::wchar_t) -> bool {
+    match std::char::from_u32(c as u32) {
+        Some(c) => c.is_alphanumeric() || c == '_',
+        None => false
+    }
+}
+
+// Keys which may produce a word character in some keyboard layout, and Backspace.
+fn break_keys() -> Vec<basic::FarKey> {
+    let mut codes: Vec<i32> = (b'A'..=b'Z').chain(b'0'..=b'9').map(|c| c as i32).collect();
+    codes.extend_from_slice(&[winuser::VK_OEM_1, winuser::VK_OEM_2, winuser::VK_OEM_3, winuser::VK_OEM_4,
+        winuser::VK_OEM_5, winuser::VK_OEM_6, winuser::VK_OEM_7, winuser::VK_OEM_8, winuser::VK_OEM_102,
+        winuser::VK_OEM_PLUS, winuser::VK_OEM_COMMA, winuser::VK_OEM_MINUS, winuser::VK_OEM_PERIOD]);
+    let mut keys: Vec<basic::FarKey> = Vec::new();
+    for code in codes {
+        keys.push(basic::FarKey { virtual_key_code: code as u16, control_key_state: 0 });
+        keys.push(basic::FarKey { virtual_key_code: code as u16, control_key_state: wincon::SHIFT_PRESSED });
+    }
+    keys.push(basic::FarKey { virtual_key_code: winuser::VK_BACK as u16, control_key_state: 0 });
+    keys
+}
+
+// Character typed by the key in the keyboard layout of the console.
+fn key_char(key: &basic::FarKey) -> Option<char> {
+    let mut key_state = [0u8; 256];
+    if key.control_key_state & wincon::SHIFT_PRESSED != 0 {
+        key_state[winuser::VK_SHIFT as usize] = 0x80;
+    }
+    let mut buffer = [0 as ffi::wchar_t; 4];
+    let length = unsafe {
+        let thread_id = winuser::GetWindowThreadProcessId(wincon::GetConsoleWindow(), ptr::null_mut());
+        let layout = winuser::GetKeyboardLayout(thread_id);
+        let scan_code = winuser::MapVirtualKeyExW(key.virtual_key_code as u32, winuser::MAPVK_VK_TO_VSC, layout);
+        winuser::ToUnicodeEx(key.virtual_key_code as u32, scan_code, key_state.as_ptr(), buffer.as_mut_ptr(),
+                             buffer.len() as i32, 0, layout)
+    };
+    match length {
+        1 => std::char::from_u32(buffer[0] as u32),
+        _ => None
+    }
+}
+
+// Shows the candidates starting with the word before the cursor in a menu placed under the cursor.
+// Typed word characters narrow the list, Backspace widens it. The chosen candidate replaces the word
+// prefix and is returned.
+pub fn show_completion(editor: &Editor, candidates: &[String]) -> Result<Option<String>> {
+    trace!(">show_completion()");
+    let info = editor.get_info()?;
+    let string = editor.get_string(None)?;
+    let text = string.text.as_slice();
+
+    let cur_pos = (info.cur_pos as usize).min(text.len());
+    let mut prefix_start = cur_pos;
+    while prefix_start > 0 && is_word_char(text[prefix_start - 1]) {
+        prefix_start -= 1;
+    }
+    let mut filter = String::from_utf16_lossy(&text[prefix_start..cur_pos]);
+
+    // the cursor position accounts for the place of the editor window, the editor relative position is
+    // used if it is unknown
+    let (x, y) = match basic::get_cursor_pos() {
+        Some((x, y)) => (x, y + 1),
+        None => {
+            let title_bar = if info.options.contains(EDITOR_OPTIONS::EOPT_SHOWTITLEBAR) { 1 } else { 0 };
+            (info.cur_tab_pos - info.left_pos, info.cur_line - info.top_screen_line + title_bar + 1)
+        }
+    };
+
+    let keys = break_keys();
+    let chosen = loop {
+        let lowercase_filter = filter.to_lowercase();
+        let filtered: Vec<&String> = candidates.iter()
+            .filter(|candidate| candidate.to_lowercase().starts_with(lowercase_filter.as_str()))
+            .collect();
+        let items: Vec<basic::FarMenuItem> = filtered.iter()
+            .map(|candidate| basic::FarMenuItem {
+                flags: basic::MENUITEMFLAGS::MIF_NONE,
+                text: WideString::from(candidate.as_str()),
+                accel_key: basic::FarKey {
+                    virtual_key_code: 0,
+                    control_key_state: 0,
+                }
+            })
+            .collect();
+
+        let (selected, break_code) = basic::menu(Some(x), Some(y), None,
+                                                 basic::FARMENUFLAGS::FMENU_WRAPMODE,
+                                                 Some(filter.as_str()), None, None,
+                                                 Some(keys.clone()),
+                                                 items);
+        match break_code {
+            Some(code) if keys[code].virtual_key_code == winuser::VK_BACK as u16 => { filter.pop(); },
+            // keys which don't type a word character are ignored
+            Some(code) => match key_char(&keys[code]) {
+                Some(c) if is_word_char(c as u32 as ffi::wchar_t) => filter.push(c),
+                _ => {}
+            },
+            None => break selected.map(|i| filtered[i].clone())
+        }
+    };
+
+    if let Some(ref chosen) = chosen {
+        let chosen_ws = WideString::from(chosen.as_str());
+        let mut line: Vec<ffi::wchar_t> = Vec::with_capacity(text.len() + chosen_ws.len());
+        line.extend_from_slice(&text[..prefix_start]);
+        line.extend_from_slice(chosen_ws.as_slice());
+        line.extend_from_slice(&text[cur_pos..]);
+
+        let _undo_group = editor.begin_undo_group()?;
+        editor.set_string(None, &line, None)?;
+        editor.set_cursor(info.cur_line as usize, prefix_start + chosen_ws.len())?;
+    }
+    trace!("<show_completion()");
+    return Ok(chosen);
+}
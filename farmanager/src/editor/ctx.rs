@@ -0,0 +1,56 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+// None marks a session taken out for the time EditorSessions::with runs
+pub(super) type Sessions<T> = Rc<RefCell<HashMap<libc::intptr_t, Option<T>>>>;
+
+trait SessionStore {
+    fn is_alive(&self) -> bool;
+    fn close(&self, editor_id: libc::intptr_t) -> Option<Box<dyn Any>>;
+}
+
+impl<T: 'static> SessionStore for Weak<RefCell<HashMap<libc::intptr_t, Option<T>>>> {
+    fn is_alive(&self) -> bool {
+        self.strong_count() > 0
+    }
+
+    fn close(&self, editor_id: libc::intptr_t) -> Option<Box<dyn Any>> {
+        let sessions = self.upgrade()?;
+        let session = sessions.borrow_mut().remove(&editor_id)??;
+        Some(Box::new(session) as Box<dyn Any>)
+    }
+}
+
+// Stores are registered per EditorSessions instance and forgotten when it is dropped
+pub(super) struct Context {
+    session_stores: Vec<Box<dyn SessionStore>>,
+}
+
+impl Context {
+
+    #[allow(dead_code)]
+    pub(super) fn register_sessions<T: 'static>(&mut self, sessions: &Sessions<T>) {
+        self.session_stores.retain(|store| store.is_alive());
+        self.session_stores.push(Box::new(Rc::downgrade(sessions)));
+    }
+
+    // Removed sessions are returned to be dropped outside of the context
+    #[allow(dead_code)]
+    pub(super) fn close_editor(&mut self, editor_id: libc::intptr_t) -> Vec<Box<dyn Any>> {
+        self.session_stores.iter()
+            .filter_map(|store| store.close(editor_id))
+            .collect()
+    }
+
+}
+
+impl Default for Context {
+
+    fn default() -> Context {
+        Context {
+            session_stores: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::ops::Range;
+
+use log::*;
+
+use crate::basic;
+use crate::misc::{REGEXP_FLAGS, RegExp};
+
+use super::*;
+
+// Texts of the confirmation message, e.g. taken from the plugin language file.
+pub struct ConfirmMessages {
+    pub title: WideString,
+    pub with: WideString,
+    pub replace: WideString,
+    pub all: WideString,
+    pub skip: WideString,
+    pub cancel: WideString,
+}
+
+pub enum ReplaceMode {
+    Preview,
+    ConfirmEach(ConfirmMessages),
+    ReplaceAll,
+}
+
+pub struct SearchMatch {
+    pub line: usize,
+    pub range: Range<usize>,
+    pub text: String,
+    pub replacement: String,
+}
+
+enum Confirmation {
+    Replace,
+    All,
+    Skip,
+    Cancel,
+}
+
+fn confirm(messages: &ConfirmMessages, found: &SearchMatch) -> Confirmation {
+    let lines = vec![
+        messages.title.clone(),
+        WideString::from(found.text.as_str()),
+        messages.with.clone(),
+        WideString::from(found.replacement.as_str()),
+        messages.replace.clone(),
+        messages.all.clone(),
+        messages.skip.clone(),
+        messages.cancel.clone(),
+    ];
+    match basic::message(basic::FARMESSAGEFLAGS::FMSG_NONE, None, basic::MessageItems::Lines(lines), 4) {
+        Some(0) => Confirmation::Replace,
+        Some(1) => Confirmation::All,
+        Some(2) => Confirmation::Skip,
+        _ => Confirmation::Cancel,
+    }
+}
+
+// Searches every line of the editor for the pattern. Replacement may refer to the captured groups as
+// $0..$9. In the Preview mode the editor is not modified, the other modes replace the matches inside
+// one undo group, which is opened by the first replacement. The cursor moved by ConfirmEach is put back.
+// Returns the found matches or the replaced ones.
+pub fn search_replace(editor: &Editor, pattern: &str, replacement: &str, flags: REGEXP_FLAGS,
+                      mode: ReplaceMode) -> Result<Vec<SearchMatch>> {
+    trace!(">search_replace()");
+    let reg_exp = RegExp::new(pattern, flags)?;
+    let info = editor.get_info()?;
+
+    let mut result: Vec<SearchMatch> = Vec::new();
+    let mut undo_group: Option<UndoGroup> = None;
+    let searched = search_lines(editor, &info, &reg_exp, replacement, &mode, &mut undo_group, &mut result);
+
+    let restored = match mode {
+        ReplaceMode::ConfirmEach(_) => editor.set_selection(&Selection::None)
+            .and_then(|_| editor.set_position(EditorSetPosition {
+                cur_line: Some(info.cur_line as usize),
+                cur_pos: Some(info.cur_pos as usize),
+                top_screen_line: Some(info.top_screen_line as usize),
+                left_pos: Some(info.left_pos as usize),
+                ..Default::default()
+            })),
+        _ => Ok(())
+    };
+    drop(undo_group);
+    searched?;
+    restored?;
+    trace!("<search_replace()");
+    return Ok(result);
+}
+
+fn search_lines(editor: &Editor, info: &EditorInfo, reg_exp: &RegExp, replacement: &str, mode: &ReplaceMode,
+                undo_group: &mut Option<UndoGroup>, result: &mut Vec<SearchMatch>) -> Result<()> {
+    let preview = match mode {
+        ReplaceMode::Preview => true,
+        _ => false
+    };
+    let mut replace_all = match mode {
+        ReplaceMode::ReplaceAll => true,
+        _ => false
+    };
+
+    'lines: for line in 0..info.total_lines as usize {
+        let string = editor.get_string(Some(line))?;
+        let text = string.text.as_slice();
+
+        let mut new_text: Vec<ffi::wchar_t> = Vec::with_capacity(text.len());
+        let mut copied = 0;
+        let mut changed = false;
+        let mut position = 0;
+        while position <= text.len() {
+            let found = match reg_exp.search(text, position) {
+                Some(found) => found,
+                None => break
+            };
+            let range = found.range();
+            position = if range.end > range.start { range.end } else { range.end + 1 };
+
+            let found = SearchMatch {
+                line,
+                range: range.clone(),
+                text: String::from_utf16_lossy(&text[range.clone()]),
+                replacement: found.expand(text, replacement),
+            };
+
+            let replace = match mode {
+                ReplaceMode::Preview => false,
+                _ if replace_all => true,
+                ReplaceMode::ConfirmEach(messages) => {
+                    let start = new_text.len() + range.start - copied;
+                    editor.set_selection(&Selection::Stream {
+                        start: Position { line, column: start },
+                        end: Position { line, column: start + range.len() },
+                    })?;
+                    editor.set_cursor(line, start)?;
+                    editor.redraw()?;
+                    match confirm(messages, &found) {
+                        Confirmation::Replace => true,
+                        Confirmation::All => {
+                            replace_all = true;
+                            true
+                        },
+                        Confirmation::Skip => false,
+                        Confirmation::Cancel => break 'lines,
+                    }
+                },
+                ReplaceMode::ReplaceAll => true,
+            };
+
+            if replace {
+                if undo_group.is_none() {
+                    *undo_group = Some(editor.begin_undo_group()?);
+                }
+                new_text.extend_from_slice(&text[copied..range.start]);
+                new_text.extend(found.replacement.encode_utf16());
+                copied = range.end;
+                changed = true;
+
+                if !replace_all {
+                    let mut current = new_text.clone();
+                    current.extend_from_slice(&text[copied..]);
+                    editor.set_string(Some(line), &current, None)?;
+                }
+            }
+            if replace || preview {
+                result.push(found);
+            }
+        }
+
+        if changed {
+            new_text.extend_from_slice(&text[copied..]);
+            editor.set_string(Some(line), &new_text, None)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use log::*;
+
+use super::*;
+
+const DEFAULT_EOL: &str = "\r\n";
+
+struct Line {
+    text: String,
+    eol: WideString,
+}
+
+// Passes the selected lines, or all lines of the file when nothing is selected, to the function and
+// writes back only the changed lines. Returns false if the function did not change anything. Column
+// blocks aren't supported and give an error, as the function gets whole lines.
+pub fn transform_lines<F>(editor: &Editor, mut func: F) -> Result<bool> where F: FnMut(&[String]) -> Vec<String> {
+    trace!(">transform_lines()");
+    let info = editor.get_info()?;
+    let selection = editor.get_selection()?;
+
+    let total_lines = info.total_lines as usize;
+    let (first, last) = match selection {
+        Selection::None => (0, total_lines - 1),
+        Selection::Stream { start, end } => (start.line, match end.column {
+            0 if end.line > start.line => end.line - 1,
+            _ => end.line
+        }.min(total_lines - 1)),
+        Selection::Column { .. } => {
+            trace!("<transform_lines()");
+            return Err(format_err!(""));
+        },
+    };
+
+    let mut old_lines: Vec<Line> = Vec::with_capacity(last - first + 1);
+    for line in first..=last {
+        let string = editor.get_string(Some(line))?;
+        old_lines.push(Line {
+            text: String::from_utf16_lossy(&string.text),
+            eol: string.eol,
+        });
+    }
+    let old_texts: Vec<String> = old_lines.iter().map(|l| l.text.clone()).collect();
+    let new_texts = func(old_texts.as_slice());
+
+    let prefix = old_texts.iter().zip(new_texts.iter())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old_texts[prefix..].iter().rev().zip(new_texts[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_changed = old_texts.len() - prefix - suffix;
+    let new_changed = new_texts.len() - prefix - suffix;
+    if old_changed == 0 && new_changed == 0 {
+        trace!("<transform_lines()");
+        return Ok(false);
+    }
+
+    let default_eol = old_lines.iter()
+        .map(|l| l.eol.clone())
+        .find(|eol| !eol.is_empty())
+        .unwrap_or_else(|| WideString::from(DEFAULT_EOL));
+    let file_eol = match last + 1 == total_lines {
+        true => Some(old_lines[old_lines.len() - 1].eol.clone()),
+        false => None
+    };
+
+    {
+        let _undo_group = editor.begin_undo_group()?;
+        let start = first + prefix;
+
+        for i in 0..old_changed.min(new_changed) {
+            if old_texts[prefix + i] != new_texts[prefix + i] {
+                editor.set_string(Some(start + i), &to_wide(&new_texts[prefix + i]), None)?;
+            }
+        }
+
+        if new_changed > old_changed {
+            let insert_at = start + old_changed;
+            let count = new_changed - old_changed;
+            insert_lines(editor, insert_at, count, total_lines)?;
+            for i in 0..count {
+                let text = to_wide(&new_texts[prefix + old_changed + i]);
+                editor.set_string(Some(insert_at + i), &text, Some(&default_eol))?;
+            }
+            if insert_at == total_lines {
+                let previous = editor.get_string(Some(insert_at - 1))?;
+                if previous.eol.is_empty() {
+                    editor.set_string(Some(insert_at - 1), &previous.text, Some(&default_eol))?;
+                }
+            }
+        } else if old_changed > new_changed {
+            let delete_at = start + new_changed;
+            editor.set_cursor(delete_at, 0)?;
+            for _ in 0..old_changed - new_changed {
+                editor.delete_string()?;
+            }
+        }
+
+        if let Some(ref file_eol) = file_eol {
+            let info = editor.get_info()?;
+            let last_line = editor.get_string(Some(info.total_lines as usize - 1))?;
+            editor.set_string(Some(info.total_lines as usize - 1), &last_line.text, Some(file_eol))?;
+        }
+    }
+
+    restore_state(editor, &info, &selection, last, new_texts.len() as isize - old_texts.len() as isize)?;
+    trace!("<transform_lines()");
+    return Ok(true);
+}
+
+// Unlike WideString keeps the NUL characters of the line.
+fn to_wide(text: &str) -> Vec<ffi::wchar_t> {
+    text.encode_utf16().collect()
+}
+
+fn insert_lines(editor: &Editor, line: usize, count: usize, total_lines: usize) -> Result<()> {
+    if line < total_lines {
+        editor.set_cursor(line, 0)?;
+    } else {
+        let previous = editor.get_string(Some(line - 1))?;
+        editor.set_cursor(line - 1, previous.text.len())?;
+    }
+    for _ in 0..count {
+        editor.insert_string(false)?;
+    }
+    return Ok(());
+}
+
+fn restore_state(editor: &Editor, info: &EditorInfo, selection: &Selection, last: usize, delta: isize) -> Result<()> {
+    let total_lines = editor.get_info()?.total_lines as usize;
+    let shift = |line: usize| ((line as isize + delta).max(0) as usize).min(total_lines - 1);
+
+    let selection = match *selection {
+        Selection::Stream { start, end } => {
+            // the end keeps its column unless the new line is shorter
+            let line = shift(end.line);
+            Selection::Stream {
+                start,
+                end: Position {
+                    line,
+                    column: end.column.min(editor.get_string(Some(line))?.text.len()),
+                },
+            }
+        },
+        selection => selection,
+    };
+
+    editor.set_position(EditorSetPosition {
+        cur_line: Some(match info.cur_line as usize {
+            cur_line if cur_line > last => shift(cur_line),
+            cur_line => cur_line.min(total_lines - 1)
+        }),
+        cur_pos: Some(info.cur_pos as usize),
+        top_screen_line: Some((info.top_screen_line as usize).min(total_lines - 1)),
+        left_pos: Some(info.left_pos as usize),
+        ..Default::default()
+    })?;
+    editor.set_selection(&selection)
+}
@@ -378,7 +378,7 @@ pub struct FarListInsert {
 }
 
 #[repr(C)] #[derive(Clone, Copy)]
-struct FarListGetItem {
+pub struct FarListGetItem {
     pub struct_size: size_t,
     pub item_index: intptr_t,
     pub item: FarListItem,
@@ -451,7 +451,7 @@ pub struct FarList {
 }
 
 #[repr(C)] #[derive(Clone, Copy)]
-struct FarListTitles {
+pub struct FarListTitles {
     pub struct_size: size_t,
     pub title_size: size_t,
     pub title: *const wchar_t,
@@ -460,7 +460,7 @@ struct FarListTitles {
 }
 
 #[repr(C)] #[derive(Clone, Copy)]
-struct FarDialogItemColors {
+pub struct FarDialogItemColors {
     pub struct_size: size_t,
     pub flags: c_ulonglong,
     pub colors_count: size_t,
@@ -2280,10 +2280,22 @@ impl PluginStartupInfo {
         (self.panel_control)(h_panel, command, param1, param2)
     }
 
+    pub fn editor_control(&self, editor_id: intptr_t, command: EDITOR_CONTROL_COMMANDS, param1: intptr_t, param2: *mut c_void) -> intptr_t {
+        (self.editor_control)(editor_id, command, param1, param2)
+    }
+
+    pub fn viewer_control(&self, viewer_id: intptr_t, command: VIEWER_CONTROL_COMMANDS, param1: intptr_t, param2: *mut c_void) -> intptr_t {
+        (self.viewer_control)(viewer_id, command, param1, param2)
+    }
+
     pub fn file_filter_control(&self, h_handle: HANDLE, command: FAR_FILE_FILTER_CONTROL_COMMANDS, param1: intptr_t, param2: *mut c_void) -> intptr_t {
         (self.file_filter_control)(h_handle, command, param1, param2)
     }
 
+    pub fn reg_exp_control(&self, h_handle: HANDLE, command: FAR_REGEXP_CONTROL_COMMANDS, param1: intptr_t, param2: *mut c_void) -> intptr_t {
+        (self.reg_exp_control)(h_handle, command, param1, param2)
+    }
+
     pub fn free_dir_list(&self, p_panel_items: *mut PluginPanelItem, n_items_number: size_t) {
         (self.free_dir_list)(p_panel_items, n_items_number)
     }
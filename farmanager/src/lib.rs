@@ -61,6 +61,12 @@ pub trait FarPlugin: panic::UnwindSafe {
     fn settings_exports(&mut self) -> Option<&mut dyn settings::ExportFunctions> {
         None
     }
+    fn editor_exports(&mut self) -> Option<&mut dyn editor::ExportFunctions> {
+        None
+    }
+    fn viewer_exports(&mut self) -> Option<&mut dyn viewer::ExportFunctions> {
+        None
+    }
 }
 
 fn init(plugin: Box<dyn FarPlugin>) {
@@ -69,12 +75,14 @@ fn init(plugin: Box<dyn FarPlugin>) {
     });
     basic::init_context();
     panel::init_context();
+    editor::init_context();
     panic::set_hook(Box::new(|info| {
         handle_panic(info.payload());
     }));
 }
 
 fn destroy() {
+    editor::cleanup_context();
     panel::cleanup_context();
     basic::cleanup_context();
     FAR_STANDARD_FUNCTIONS.with(|ref_cell: &RefCell<Option<*mut ffi::FarStandardFunctions>>| {
@@ -1,10 +1,17 @@
+use std::ops::Range;
+use std::ptr;
+
+use bitflags::bitflags;
+use failure::*;
 use log::*;
 
 use crate::basic;
+use crate::common::string::WideString;
 use crate::far_api;
 use crate::ffi;
 pub use crate::ffi::COLORDIALOGFLAGS as COLORDIALOGFLAGS;
 pub use crate::ffi::rgba as rgba;
+use crate::Result;
 
 #[allow(unused_variables)]
 pub fn show_color_chooser_dialog(flags: COLORDIALOGFLAGS) -> Option<ffi::FarColor> {
@@ -28,3 +35,190 @@ pub fn show_color_chooser_dialog(flags: COLORDIALOGFLAGS) -> Option<ffi::FarColo
     trace!("<show_color_chooser_dialog()");
     return result;
 }
+
+bitflags! {
+    #[allow(non_camel_case_types)]
+    pub struct REGEXP_FLAGS: u32 {
+        const IGNORE_CASE = 0x01;
+        const MULTILINE   = 0x02;
+        const SINGLE_LINE = 0x04;
+        const EXTENDED    = 0x08;
+    }
+}
+
+pub struct RegExp {
+    handle: ffi::HANDLE,
+    brackets_count: usize,
+}
+
+pub struct RegExpMatch {
+    groups: Vec<Option<Range<usize>>>,
+}
+
+impl RegExpMatch {
+
+    pub fn range(&self) -> Range<usize> {
+        match self.groups[0] {
+            Some(ref range) => range.clone(),
+            None => unreachable!()
+        }
+    }
+
+    pub fn group(&self, index: usize) -> Option<Range<usize>> {
+        self.groups.get(index).and_then(|group| group.clone())
+    }
+
+    pub fn groups_count(&self) -> usize {
+        self.groups.len()
+    }
+
+    // Substitutes $0..$9 with the captured groups, $$ with $
+    pub fn expand(&self, text: &[ffi::wchar_t], replacement: &str) -> String {
+        let mut result = String::new();
+        let mut chars = replacement.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                result.push(c);
+                continue;
+            }
+            match chars.peek().cloned() {
+                Some('$') => {
+                    chars.next();
+                    result.push('$');
+                },
+                Some(d) if d.is_ascii_digit() => {
+                    chars.next();
+                    if let Some(group) = self.group(d.to_digit(10).unwrap() as usize) {
+                        result.push_str(String::from_utf16_lossy(&text[group]).as_str());
+                    }
+                },
+                _ => result.push('$')
+            }
+        }
+        result
+    }
+}
+
+impl RegExp {
+
+    pub fn new(pattern: &str, flags: REGEXP_FLAGS) -> Result<RegExp> {
+        trace!(">new()");
+        let mut options = String::new();
+        if flags.contains(REGEXP_FLAGS::IGNORE_CASE) { options.push('i'); }
+        if flags.contains(REGEXP_FLAGS::MULTILINE) { options.push('m'); }
+        if flags.contains(REGEXP_FLAGS::SINGLE_LINE) { options.push('s'); }
+        if flags.contains(REGEXP_FLAGS::EXTENDED) { options.push('x'); }
+        let pattern = WideString::from(format!("/{}/{}", pattern, options));
+
+        let mut handle: ffi::HANDLE = ptr::null_mut();
+        let created = far_api(|far_api: &mut ffi::PluginStartupInfo| {
+            far_api.reg_exp_control(ptr::null_mut(),
+                                    ffi::FAR_REGEXP_CONTROL_COMMANDS::RECTL_CREATE,
+                                    0,
+                                    &mut handle as *mut _ as *mut libc::c_void)
+        });
+        if created == 0 {
+            trace!("<new()");
+            return Err(format_err!(""));
+        }
+
+        let mut reg_exp = RegExp {
+            handle,
+            brackets_count: 0,
+        };
+        if reg_exp.control(ffi::FAR_REGEXP_CONTROL_COMMANDS::RECTL_COMPILE, 0, pattern.as_ptr() as *mut libc::c_void) == 0 {
+            trace!("<new()");
+            return Err(format_err!(""));
+        }
+        reg_exp.control(ffi::FAR_REGEXP_CONTROL_COMMANDS::RECTL_OPTIMIZE, 0, ptr::null_mut());
+        reg_exp.brackets_count = reg_exp.control(ffi::FAR_REGEXP_CONTROL_COMMANDS::RECTL_BRACKETSCOUNT, 0, ptr::null_mut()) as usize;
+        trace!("<new()");
+        return Ok(reg_exp);
+    }
+
+    pub fn brackets_count(&self) -> usize {
+        self.brackets_count
+    }
+
+    pub fn search(&self, text: &[ffi::wchar_t], position: usize) -> Option<RegExpMatch> {
+        self.exec(ffi::FAR_REGEXP_CONTROL_COMMANDS::RECTL_SEARCHEX, text, position)
+    }
+
+    pub fn match_at(&self, text: &[ffi::wchar_t], position: usize) -> Option<RegExpMatch> {
+        self.exec(ffi::FAR_REGEXP_CONTROL_COMMANDS::RECTL_MATCHEX, text, position)
+    }
+
+    fn exec(&self, command: ffi::FAR_REGEXP_CONTROL_COMMANDS, text: &[ffi::wchar_t], position: usize) -> Option<RegExpMatch> {
+        let mut matches: Vec<ffi::RegExpMatch> = vec![ffi::RegExpMatch { start: -1, end: -1 }; self.brackets_count.max(1)];
+        let mut search = ffi::RegExpSearch {
+            text: text.as_ptr(),
+            position: position as libc::intptr_t,
+            length: text.len() as libc::intptr_t,
+            regexp_match: matches.as_mut_ptr(),
+            count: matches.len() as libc::intptr_t,
+            reserved: ptr::null_mut(),
+        };
+
+        match self.control(command, 0, &mut search as *mut _ as *mut libc::c_void) {
+            0 => None,
+            _ => Some(RegExpMatch {
+                groups: matches.iter()
+                    .map(|m| if m.start < 0 || m.end < 0 { None } else { Some(m.start as usize..m.end as usize) })
+                    .collect()
+            })
+        }
+    }
+
+    fn control(&self, command: ffi::FAR_REGEXP_CONTROL_COMMANDS, param1: libc::intptr_t, param2: *mut libc::c_void) -> libc::intptr_t {
+        far_api(|far_api: &mut ffi::PluginStartupInfo| {
+            far_api.reg_exp_control(self.handle, command, param1, param2)
+        })
+    }
+}
+
+impl Drop for RegExp {
+    fn drop(&mut self) {
+        let _ = self.control(ffi::FAR_REGEXP_CONTROL_COMMANDS::RECTL_FREE, 0, ptr::null_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wide(text: &str) -> Vec<ffi::wchar_t> {
+        text.encode_utf16().collect()
+    }
+
+    fn digits_match() -> RegExpMatch {
+        let mut groups = vec![Some(0..10)];
+        groups.extend((0..9).map(|i| Some(i..i + 1)));
+        RegExpMatch { groups }
+    }
+
+    #[test]
+    fn expand_substitutes_every_group() {
+        let text = wide("abcdefghij");
+        let found = digits_match();
+        assert_eq!(found.expand(&text, "$0"), "abcdefghij");
+        assert_eq!(found.expand(&text, "$1$2$3$4$5$6$7$8$9"), "abcdefghi");
+        assert_eq!(found.expand(&text, "<$9-$1>"), "<i-a>");
+    }
+
+    #[test]
+    fn expand_escapes_dollar() {
+        let text = wide("abcdefghij");
+        let found = digits_match();
+        assert_eq!(found.expand(&text, "$$1"), "$1");
+        assert_eq!(found.expand(&text, "a$$"), "a$");
+        assert_eq!(found.expand(&text, "$x$"), "$x$");
+    }
+
+    #[test]
+    fn expand_skips_missing_groups() {
+        let text = wide("key=value");
+        let found = RegExpMatch { groups: vec![Some(0..9), Some(0..3), None] };
+        assert_eq!(found.expand(&text, "$1:$2"), "key:");
+        assert_eq!(found.expand(&text, "[$7]"), "[]");
+    }
+}
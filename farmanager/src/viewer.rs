@@ -1,15 +1,432 @@
+use std::mem;
+use std::ops::Range;
+#[allow(unused_imports)] // till IntelliJ Rust stop to highlight is as an error
+use std::panic;
+use std::ptr;
+
+use failure::*;
 use log::*;
 
 use crate::common::string::WideString;
 use crate::far_api;
+#[allow(unused_imports)] // TODO remove after IntelliJ Rust will stop to highlight is as an error
+use crate::FarPlugin;
 use crate::ffi;
+pub use crate::ffi::VIEWER_FLAGS as VIEWER_FLAGS;
+pub use crate::ffi::VIEWER_OPTIONS as VIEWER_OPTIONS;
+pub use crate::ffi::VIEWER_SETPOS_FLAGS as VIEWER_SETPOS_FLAGS;
+#[allow(unused_imports)] // TODO remove after IntelliJ Rust will stop to highlight is as an error
+use crate::plugin;
+use crate::Result;
+
+pub trait ExportFunctions {
+
+    #[allow(unused_variables)]
+    fn process_viewer_event(&mut self, info: ProcessViewerEventInfo) {}
+}
+
+// The mode is queried from the viewer, it is None if Far didn't return it.
+pub enum ViewerEvent {
+    Read {
+        mode: Option<ViewerMode>,
+    },
+    Close,
+    GotFocus {
+        mode: Option<ViewerMode>,
+    },
+    KillFocus,
+}
+
+pub struct ProcessViewerEventInfo {
+    pub event: ViewerEvent,
+    pub viewer_id: libc::intptr_t,
+}
+
+impl ProcessViewerEventInfo {
+    #[allow(dead_code)]
+    fn from(info: &ffi::ProcessViewerEventInfo) -> Option<Self> {
+        let viewer = Viewer::from(info.viewer_id);
+        let event = match info.event {
+            e if e == ffi::VIEWER_EVENTS::VE_READ as libc::intptr_t => ViewerEvent::Read {
+                mode: viewer.get_mode().ok(),
+            },
+            e if e == ffi::VIEWER_EVENTS::VE_CLOSE as libc::intptr_t => ViewerEvent::Close,
+            e if e == ffi::VIEWER_EVENTS::VE_GOTFOCUS as libc::intptr_t => ViewerEvent::GotFocus {
+                mode: viewer.get_mode().ok(),
+            },
+            e if e == ffi::VIEWER_EVENTS::VE_KILLFOCUS as libc::intptr_t => ViewerEvent::KillFocus,
+            _ => return None
+        };
+        Some(ProcessViewerEventInfo {
+            event,
+            viewer_id: info.viewer_id,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ViewMode {
+    Text,
+    Hex,
+    Dump,
+}
+
+impl From<ffi::VIEWER_MODE_TYPE> for ViewMode {
+    fn from(mode: ffi::VIEWER_MODE_TYPE) -> Self {
+        match mode {
+            ffi::VIEWER_MODE_TYPE::VMT_TEXT => ViewMode::Text,
+            ffi::VIEWER_MODE_TYPE::VMT_HEX => ViewMode::Hex,
+            ffi::VIEWER_MODE_TYPE::VMT_DUMP => ViewMode::Dump,
+        }
+    }
+}
+
+impl Into<ffi::VIEWER_MODE_TYPE> for ViewMode {
+    fn into(self) -> ffi::VIEWER_MODE_TYPE {
+        match self {
+            ViewMode::Text => ffi::VIEWER_MODE_TYPE::VMT_TEXT,
+            ViewMode::Hex => ffi::VIEWER_MODE_TYPE::VMT_HEX,
+            ViewMode::Dump => ffi::VIEWER_MODE_TYPE::VMT_DUMP,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ViewerMode {
+    pub code_page: libc::uintptr_t,
+    pub wrap: bool,
+    pub word_wrap: bool,
+    pub view_mode: ViewMode,
+}
+
+impl From<&ffi::ViewerMode> for ViewerMode {
+    fn from(mode: &ffi::ViewerMode) -> Self {
+        ViewerMode {
+            code_page: mode.code_page,
+            wrap: mode.flags.contains(ffi::VIEWER_MODE_FLAGS::VMF_WRAP),
+            word_wrap: mode.flags.contains(ffi::VIEWER_MODE_FLAGS::VMF_WORDWRAP),
+            view_mode: ViewMode::from(mode.view_mode),
+        }
+    }
+}
+
+pub struct ViewerInfo {
+    pub viewer_id: libc::intptr_t,
+    pub file_name: WideString,
+    pub tab_size: libc::intptr_t,
+    pub cur_mode: ViewerMode,
+    pub file_size: i64,
+    pub file_pos: i64,
+    pub left_pos: i64,
+    pub options: VIEWER_OPTIONS,
+    pub window_size_x: libc::intptr_t,
+    pub window_size_y: libc::intptr_t,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct ViewerPosition {
+    pub file_pos: i64,
+    pub left_pos: i64,
+}
+
+#[derive(Clone, Copy)]
+pub struct Viewer {
+    id: libc::intptr_t
+}
+
+impl From<libc::intptr_t> for Viewer {
+    fn from(id: libc::intptr_t) -> Self {
+        Viewer {
+            id
+        }
+    }
+}
+
+impl Viewer {
+
+    // there is no CURRENT_VIEWER constant in plugin.hpp, -1 is used by Far for the current viewer
+    pub fn current() -> Viewer {
+        Viewer::from(-1)
+    }
+
+    pub fn id(&self) -> libc::intptr_t {
+        self.id
+    }
+
+    pub fn get_info(&self) -> Result<ViewerInfo> {
+        trace!(">get_info()");
+        let result = self.get_raw_info().and_then(|info| {
+            self.get_file_name().map(|file_name| ViewerInfo {
+                viewer_id: info.viewer_id,
+                file_name,
+                tab_size: info.tab_size,
+                cur_mode: ViewerMode::from(&info.cur_mode),
+                file_size: info.file_size,
+                file_pos: info.file_pos,
+                left_pos: info.left_pos,
+                options: info.options,
+                window_size_x: info.window_size_x,
+                window_size_y: info.window_size_y,
+            })
+        });
+        trace!("<get_info()");
+        return result;
+    }
+
+    pub fn get_mode(&self) -> Result<ViewerMode> {
+        trace!(">get_mode()");
+        let result = self.get_raw_info().map(|info| ViewerMode::from(&info.cur_mode));
+        trace!("<get_mode()");
+        return result;
+    }
+
+    pub fn get_file_name(&self) -> Result<WideString> {
+        trace!(">get_file_name()");
+        let size = self.control(ffi::VIEWER_CONTROL_COMMANDS::VCTL_GETFILENAME,
+                                0,
+                                ptr::null_mut());
+        if size <= 0 {
+            trace!("<get_file_name()");
+            return Err(format_err!(""));
+        }
+
+        let mut buf: Vec<ffi::wchar_t> = vec![0; size as usize];
+        let result = match self.control(ffi::VIEWER_CONTROL_COMMANDS::VCTL_GETFILENAME,
+                                         buf.len() as libc::intptr_t,
+                                         buf.as_mut_ptr() as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(WideString::from(buf.as_slice()))
+        };
+        trace!("<get_file_name()");
+        return result;
+    }
+
+    // Returns the resulting position unless VSP_NORETNEWPOS is passed.
+    pub fn set_position(&self, position: ViewerPosition, flags: VIEWER_SETPOS_FLAGS) -> Result<ViewerPosition> {
+        trace!(">set_position()");
+        let mut vsp = ffi::ViewerSetPosition {
+            struct_size: mem::size_of::<ffi::ViewerSetPosition>(),
+            flags,
+            start_pos: position.file_pos,
+            left_pos: position.left_pos,
+        };
+
+        let result = match self.control(ffi::VIEWER_CONTROL_COMMANDS::VCTL_SETPOSITION,
+                                         0,
+                                         &mut vsp as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(ViewerPosition {
+                file_pos: vsp.start_pos,
+                left_pos: vsp.left_pos,
+            })
+        };
+        trace!("<set_position()");
+        return result;
+    }
+
+    // Selects the byte range of the file, None removes the selection.
+    pub fn select(&self, range: Option<Range<u64>>) -> Result<()> {
+        trace!(">select()");
+        let mut vs = ffi::ViewerSelect {
+            struct_size: mem::size_of::<ffi::ViewerSelect>(),
+            block_start_pos: 0,
+            block_len: 0,
+        };
+        let param2 = match range {
+            Some(range) => {
+                vs.block_start_pos = range.start as i64;
+                vs.block_len = (range.end.max(range.start) - range.start) as i64;
+                &mut vs as *mut _ as *mut libc::c_void
+            },
+            None => ptr::null_mut()
+        };
+
+        let result = match self.control(ffi::VIEWER_CONTROL_COMMANDS::VCTL_SELECT, 0, param2) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<select()");
+        return result;
+    }
+
+    pub fn set_view_mode(&self, mode: ViewMode) -> Result<()> {
+        trace!(">set_view_mode()");
+        let view_mode: ffi::VIEWER_MODE_TYPE = mode.into();
+        let result = self.set_mode(ffi::VIEWER_SETMODE_TYPES::VSMT_VIEWMODE, view_mode as libc::intptr_t);
+        trace!("<set_view_mode()");
+        return result;
+    }
+
+    pub fn set_wrap(&self, wrap: bool) -> Result<()> {
+        trace!(">set_wrap()");
+        let result = self.set_mode(ffi::VIEWER_SETMODE_TYPES::VSMT_WRAP, wrap as libc::intptr_t);
+        trace!("<set_wrap()");
+        return result;
+    }
+
+    pub fn set_word_wrap(&self, word_wrap: bool) -> Result<()> {
+        trace!(">set_word_wrap()");
+        let result = self.set_mode(ffi::VIEWER_SETMODE_TYPES::VSMT_WORDWRAP, word_wrap as libc::intptr_t);
+        trace!("<set_word_wrap()");
+        return result;
+    }
+
+    pub fn redraw(&self) -> Result<()> {
+        trace!(">redraw()");
+        let result = match self.control(ffi::VIEWER_CONTROL_COMMANDS::VCTL_REDRAW,
+                                         0,
+                                         ptr::null_mut()) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<redraw()");
+        return result;
+    }
+
+    pub fn quit(&self) -> Result<()> {
+        trace!(">quit()");
+        let result = match self.control(ffi::VIEWER_CONTROL_COMMANDS::VCTL_QUIT,
+                                         0,
+                                         ptr::null_mut()) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        };
+        trace!("<quit()");
+        return result;
+    }
+
+    fn get_raw_info(&self) -> Result<ffi::ViewerInfo> {
+        let mut info: ffi::ViewerInfo = unsafe { mem::zeroed() };
+        info.struct_size = mem::size_of::<ffi::ViewerInfo>();
+
+        match self.control(ffi::VIEWER_CONTROL_COMMANDS::VCTL_GETINFO,
+                           0,
+                           &mut info as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(info)
+        }
+    }
+
+    fn set_mode(&self, mode_type: ffi::VIEWER_SETMODE_TYPES, value: libc::intptr_t) -> Result<()> {
+        let mut vsm = ffi::ViewerSetMode {
+            struct_size: mem::size_of::<ffi::ViewerSetMode>(),
+            mode_type,
+            param: ffi::ViewerSetModeParam { i_param: value },
+            flags: ffi::VIEWER_SETMODEFLAGS_TYPES::VSMFL_REDRAW,
+        };
+        match self.control(ffi::VIEWER_CONTROL_COMMANDS::VCTL_SETMODE,
+                           0,
+                           &mut vsm as *mut _ as *mut libc::c_void) {
+            0 => Err(format_err!("")),
+            _ => Ok(())
+        }
+    }
+
+    fn control(&self, command: ffi::VIEWER_CONTROL_COMMANDS, param1: libc::intptr_t, param2: *mut libc::c_void) -> libc::intptr_t {
+        far_api(|far_api: &mut ffi::PluginStartupInfo| {
+            far_api.viewer_control(self.id, command, param1, param2)
+        })
+    }
+}
+
+pub enum ViewerOpenResult {
+    Failed,
+    // modal viewer is closed when Far returns
+    Closed,
+    // non-modal viewer stays open. It is known only when opened with VF_IMMEDIATERETURN, otherwise Far
+    // returns after the viewer window loop and the current viewer is not the opened one.
+    Opened(Option<Viewer>),
+}
+
+pub struct ViewerOpenOptions {
+    file_name: WideString,
+    title: Option<WideString>,
+    x1: libc::intptr_t,
+    y1: libc::intptr_t,
+    x2: libc::intptr_t,
+    y2: libc::intptr_t,
+    flags: VIEWER_FLAGS,
+    code_page: libc::uintptr_t,
+}
+
+impl ViewerOpenOptions {
+
+    pub fn new(file_name: WideString) -> Self {
+        ViewerOpenOptions {
+            file_name,
+            title: None,
+            x1: 0,
+            y1: 0,
+            x2: -1,
+            y2: -1,
+            flags: VIEWER_FLAGS::VF_NONE,
+            code_page: ffi::CP_DEFAULT,
+        }
+    }
+
+    pub fn title(mut self, title: WideString) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    pub fn rect(mut self, x1: isize, y1: isize, x2: isize, y2: isize) -> Self {
+        self.x1 = x1;
+        self.y1 = y1;
+        self.x2 = x2;
+        self.y2 = y2;
+        self
+    }
+
+    pub fn flags(mut self, flags: VIEWER_FLAGS) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn code_page(mut self, code_page: libc::uintptr_t) -> Self {
+        self.code_page = code_page;
+        self
+    }
+
+    pub fn open(self) -> ViewerOpenResult {
+        trace!(">open()");
+        let return_code = far_api(|far_api: &mut ffi::PluginStartupInfo| {
+            far_api.viewer(self.file_name.as_ptr(),
+                           self.title.as_ref().map_or(ptr::null(), |t| t.as_ptr()),
+                           self.x1, self.y1, self.x2, self.y2,
+                           self.flags,
+                           self.code_page)
+        });
+        let result = match return_code {
+            0 => ViewerOpenResult::Failed,
+            _ if !self.flags.contains(VIEWER_FLAGS::VF_NONMODAL) => ViewerOpenResult::Closed,
+            _ => ViewerOpenResult::Opened(match self.flags.contains(VIEWER_FLAGS::VF_IMMEDIATERETURN) {
+                true => Viewer::current().get_raw_info().ok().map(|info| Viewer::from(info.viewer_id)),
+                false => None
+            }),
+        };
+        trace!("<open()");
+        return result;
+    }
+}
+
+#[allow(unused_variables)]
+#[cfg(feature = "viewer")]
+#[no_mangle]
+#[export_name="ProcessViewerEventW"]
+pub extern "system" fn process_viewer_event(info: *const ffi::ProcessViewerEventInfo) -> libc::intptr_t {
+    trace!(">process_viewer_event()");
+    let _ = panic::catch_unwind(|| {
+        let info_ref = unsafe { &*info };
+        assert_eq!(info_ref.struct_size, mem::size_of::<ffi::ProcessViewerEventInfo>());
 
-// TODO review and update
-pub fn open_viewer(path: WideString) {
-    trace!(">open_viewer()");
-    far_api(|far_api: &mut ffi::PluginStartupInfo| {
-        let file_name = WideString::from(path);
-        far_api.viewer(file_name.as_ptr(), 0 as *const ffi::wchar_t, 0, 0, -1, -1, ffi::VIEWER_FLAGS::VF_NONE, ffi::CP_DEFAULT);
+        if let Some(process_viewer_event_info) = ProcessViewerEventInfo::from(info_ref) {
+            plugin(|plugin: &mut dyn FarPlugin| {
+                if let Some(exports) = plugin.viewer_exports() {
+                    exports.process_viewer_event(process_viewer_event_info);
+                }
+            });
+        }
     });
-    trace!("<open_viewer()");
+    trace!("<process_viewer_event()");
+    return 0;
 }